@@ -7,14 +7,17 @@ cfg_if::cfg_if! {
   }
 }
 
+use json_pop::brackets::Bracketed;
 use json_pop::lex::Token;
-use json_pop::parser::jsonParser as parser;
+use json_pop::ser;
 use json_pop::value;
 use logos::Logos;
 
 use std::io;
 use std::io::BufRead;
 use std::io::Read;
+use std::io::Write as _;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 arg_enum! {
@@ -23,18 +26,45 @@ arg_enum! {
     enum Mode {
       lex,
       parse,
+      canonical,
+      diff,
     }
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "options", about = "json-pop options.")]
 struct Opts {
-    /// whether to lex or parse
+    /// whether to lex, parse, write the canonical form (RFC 8785), or diff two files
     #[structopt(possible_values = &Mode::variants(), case_insensitive = true, default_value = "parse")]
     mode: Mode,
+    /// the files to parse rather than stdin, or the old and new files to compare in diff mode.
+    #[structopt(parse(from_os_str))]
+    files: Vec<PathBuf>,
     /// parse each line as a separate json file.
     #[structopt(short, long)]
     line: bool,
+    /// write parsed values indented over multiple lines.
+    #[structopt(short, long)]
+    pretty: bool,
+    /// the number of spaces to indent by with --pretty.
+    #[structopt(long, default_value = "2")]
+    indent: usize,
+    /// indent with tabs rather than spaces with --pretty.
+    #[structopt(long)]
+    tab: bool,
+    /// write only the value at this JSON pointer, e.g. /spec/containers/0/image.
+    #[structopt(long)]
+    pointer: Option<String>,
+    /// in diff mode, treat objects with members in a different order as equal.
+    #[structopt(long)]
+    ignore_order: bool,
+    /// report every error rather than stopping at the first.
+    #[structopt(long)]
+    recover: bool,
+    /// write the input with suggested fixes for common mistakes applied,
+    /// errors left after fixing are reported in the fixed text.
+    #[structopt(long)]
+    fix: bool,
 
     #[cfg(feature = "pretty_errors")]
     #[structopt(
@@ -46,43 +76,129 @@ struct Opts {
     pub color: ColorArg,
 }
 
+impl Opts {
+    fn ser_config(&self) -> ser::Config {
+        if self.pretty {
+            ser::Config {
+                indent: Some(if self.tab {
+                    ser::Indent::Tab
+                } else {
+                    ser::Indent::Spaces(self.indent)
+                }),
+                ..ser::Config::pretty()
+            }
+        } else {
+            ser::Config {
+                trailing_newline: true,
+                ..ser::Config::default()
+            }
+        }
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     let opt = Opts::from_args();
     match opt.mode {
-        Mode::parse => {
-            if opt.line {
-                parse_stdin_line()
+        Mode::parse | Mode::canonical => {
+            if !opt.files.is_empty() {
+                parse_files(&opt)
+            } else if opt.line {
+                parse_stdin_line(&opt)
             } else {
-                parse_stdin()
+                parse_stdin(&opt)
             }
         }
         Mode::lex => lex_stdin_lalr(),
+        Mode::diff => diff_files(&opt),
+    }
+}
+
+fn parse<'a>(opt: &Opts, source: &'a str) -> Result<value::Value<'a>, Vec<Bracketed<'a>>> {
+    if opt.recover {
+        json_pop::parse_str_recovering(source, &Default::default()).into_result()
+    } else {
+        json_pop::parse_str_bracketed(source, &Default::default()).map_err(|error| vec![error])
     }
 }
 
-fn parse_stdin() -> anyhow::Result<()> {
+fn diff_files(opt: &Opts) -> anyhow::Result<()> {
+    let (old_name, new_name) = match &opt.files[..] {
+        [old, new] => (old.to_string_lossy(), new.to_string_lossy()),
+        _ => anyhow::bail!("diff mode takes two files, the old and the new"),
+    };
+    let old_source = std::fs::read_to_string(&opt.files[0])?;
+    let new_source = std::fs::read_to_string(&opt.files[1])?;
+    let old = parse(opt, &old_source)
+        .or_else(|errors| report_parse_errors(opt, &old_name, &old_source, errors))?;
+    let new = parse(opt, &new_source)
+        .or_else(|errors| report_parse_errors(opt, &new_name, &new_source, errors))?;
+    let options = json_pop::diff::DiffOptions {
+        ignore_member_order: opt.ignore_order,
+    };
+    let changes = json_pop::diff::compare(&old, &new, &options);
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "pretty_errors")] {
+          let writer = StandardStream::stdout(opt.color.into());
+          let config = codespan_reporting::term::Config::default();
+          let (files, diagnostics) = json_pop::extra::codespan::from_changes(
+              (&old_name, &old_source),
+              (&new_name, &new_source),
+              &changes,
+          );
+          for diagnostic in &diagnostics {
+              term::emit(&mut writer.lock(), &config, &files, diagnostic)?;
+          }
+      } else {
+          for change in &changes {
+              println!("{}", change);
+          }
+      }
+    }
+    if !changes.is_empty() {
+        anyhow::bail!("{} and {} differ", old_name, new_name);
+    }
+    Ok(())
+}
+
+fn parse_stdin(opt: &Opts) -> anyhow::Result<()> {
     let mut buffer = String::new();
     let stdin = io::stdin();
     let mut handle = stdin.lock();
 
     handle.read_to_string(&mut buffer)?;
-    let tokens = Token::lexer(&buffer).spanned().map(Token::to_lalr_triple);
-    let parsed = parser::new().parse(tokens);
-    display_value_or_error(&buffer, parsed)
+    if opt.fix {
+        let (name, fixed) = apply_fixes("stdin", buffer);
+        return match parse(opt, &fixed) {
+            Ok(_) => {
+                print!("{}", fixed);
+                Ok(())
+            }
+            Err(errors) => report_parse_errors(opt, &name, &fixed, errors),
+        };
+    }
+    let parsed = parse(opt, &buffer);
+    display_value_or_error(opt, &buffer, parsed)
 }
 
 // each line is parsed as though it were a valid json object
 // It fails to parse things like: "{ \n "foo" : "bar" }"
 // since "{\n" isn't a valid json object.
-fn parse_stdin_line() -> anyhow::Result<()> {
+fn parse_stdin_line(opt: &Opts) -> anyhow::Result<()> {
     let reader = io::BufReader::new(io::stdin());
     for input_line in reader.lines() {
         let input_line = input_line?;
-        let tokens = Token::lexer(&input_line.as_str())
-            .spanned()
-            .map(Token::to_lalr_triple);
-        let parsed = parser::new().parse(tokens);
-        if let Some(_) = display_value_or_error(&input_line, parsed).ok() {
+        if opt.fix {
+            let (name, fixed) = apply_fixes("stdin", input_line);
+            match parse(opt, &fixed) {
+                Ok(_) => println!("{}", fixed),
+                Err(errors) => {
+                    let _ = report_parse_errors::<()>(opt, &name, &fixed, errors);
+                }
+            }
+            continue;
+        }
+        let parsed = parse(opt, &input_line);
+        if display_value_or_error(opt, &input_line, parsed).is_ok() {
             continue;
         }
     }
@@ -103,26 +219,161 @@ fn lex_stdin_lalr() -> anyhow::Result<()> {
 }
 
 fn display_value_or_error(
+    opt: &Opts,
     _source: &str,
-    parsed: Result<value::Value, json_pop::parser::ParseError>,
+    parsed: Result<value::Value, Vec<Bracketed>>,
 ) -> anyhow::Result<()> {
     match parsed {
-        Ok(value) => println!("{}", value),
-        Err(error) => {
-            cfg_if::cfg_if! {
-              if #[cfg(feature = "pretty_errors")] {
-                   let opts = Opts::from_args();
-                   let writer = StandardStream::stderr(opts.color.into());
-                   let config = codespan_reporting::term::Config::default();
-                   let (files, diagnostic) = json_pop::extra::codespan::from_parse_error("stdin", &_source, &error);
-                  term::emit(&mut writer.lock(), &config, &files, &diagnostic)?;
-              } else {
-                  use std::io::Write;
-                  write!(io::stderr().lock(), "{:#?}", error)?
-              }
+        Ok(value) => display_value(opt, &value)?,
+        Err(errors) => report_parse_errors(opt, "stdin", _source, errors)?,
+    }
+    Ok(())
+}
+
+fn display_value(opt: &Opts, value: &value::Value) -> anyhow::Result<()> {
+    let value = match &opt.pointer {
+        Some(pointer) => value.pointer(pointer)?,
+        None => value,
+    };
+    match opt.mode {
+        Mode::canonical => {
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            json_pop::stringify_canonical(&mut stdout, value)?;
+            if opt.line || !opt.files.is_empty() {
+                writeln!(stdout)?;
+            }
+        }
+        _ => {
+            let config = opt.ser_config();
+            print!(
+                "{}",
+                ser::Formatted {
+                    value,
+                    config: &config
+                }
+            )
+        }
+    }
+    Ok(())
+}
+
+/// The source with the fixes of `--fix` applied, and the name to report
+/// any errors left in it under.  Their spans are in the fixed text,
+/// which the name says when it differs from the source.
+fn apply_fixes(name: &str, source: String) -> (String, String) {
+    let (fixed, applied) = json_pop::fix::fix(&source);
+    if applied.is_empty() || json_pop::parse_str(&fixed).is_ok() {
+        (name.to_string(), fixed)
+    } else {
+        let fixes = if applied.len() == 1 { "fix" } else { "fixes" };
+        (
+            format!("{} (with {} {} applied)", name, applied.len(), fixes),
+            fixed,
+        )
+    }
+}
+
+/// Parses each of the files, reporting the errors in all of them together.
+fn parse_files(opt: &Opts) -> anyhow::Result<()> {
+    let mut sources = Vec::with_capacity(opt.files.len());
+    for path in &opt.files {
+        let name = path.to_string_lossy().into_owned();
+        let source = std::fs::read_to_string(path)?;
+        sources.push(if opt.fix {
+            apply_fixes(&name, source)
+        } else {
+            (name, source)
+        });
+    }
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "pretty_errors")] {
+          use json_pop::extra::codespan;
+          let mut files = codespan_reporting::files::SimpleFiles::new();
+          let mut diagnostics = Vec::new();
+          let mut summary = codespan::Summary::default();
+      } else {
+          use json_pop::error::{JsonPopError, Located};
+          let mut failed = 0;
+      }
+    }
+    for (_name, source) in &sources {
+        match parse(opt, source) {
+            Ok(_) if opt.fix => {
+                println!("{}", source.trim_end());
+                #[cfg(feature = "pretty_errors")]
+                summary.add_file(&[]);
+            }
+            Ok(value) => {
+                display_value(opt, &value)?;
+                #[cfg(feature = "pretty_errors")]
+                summary.add_file(&[]);
+            }
+            Err(errors) => {
+                cfg_if::cfg_if! {
+                  if #[cfg(feature = "pretty_errors")] {
+                      let found = codespan::add_parse_errors(&mut files, _name.as_str(), source.as_str(), &errors);
+                      summary.add_file(&found);
+                      diagnostics.extend(found);
+                  } else {
+                      let index = json_pop::position::LineIndex::new(source);
+                      for error in errors {
+                          let error = JsonPopError::from(error);
+                          eprintln!("{}: {}", _name, Located { error: &error, index: &index });
+                      }
+                      failed += 1;
+                  }
+                }
             }
-            anyhow::bail!("Parse error");
         }
     }
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "pretty_errors")] {
+          let writer = StandardStream::stderr(opt.color.into());
+          let config = codespan_reporting::term::Config::default();
+          for diagnostic in &diagnostics {
+              term::emit(&mut writer.lock(), &config, &files, diagnostic)?;
+          }
+          if summary.failed > 0 {
+              anyhow::bail!("{}", summary);
+          }
+      } else {
+          if failed > 0 {
+              anyhow::bail!("{} of {} files failed to parse", failed, sources.len());
+          }
+      }
+    }
     Ok(())
 }
+
+fn report_parse_errors<T>(
+    _opt: &Opts,
+    _filename: &str,
+    _source: &str,
+    errors: Vec<Bracketed>,
+) -> anyhow::Result<T> {
+    let count = errors.len();
+    cfg_if::cfg_if! {
+      if #[cfg(feature = "pretty_errors")] {
+           let writer = StandardStream::stderr(_opt.color.into());
+           let config = codespan_reporting::term::Config::default();
+           let (files, diagnostics) = json_pop::extra::codespan::from_parse_errors(_filename, &_source, &errors);
+           for diagnostic in &diagnostics {
+               term::emit(&mut writer.lock(), &config, &files, diagnostic)?;
+           }
+      } else {
+          use json_pop::error::{JsonPopError, Located};
+          let index = json_pop::position::LineIndex::new(_source);
+          // Errors in stdin as given aren't named.
+          let prefix = if _filename == "stdin" { String::new() } else { format!("{}: ", _filename) };
+          for error in errors {
+              let error = JsonPopError::from(error);
+              writeln!(io::stderr().lock(), "{}{}", prefix, Located { error: &error, index: &index })?
+          }
+      }
+    }
+    if count > 1 {
+        anyhow::bail!("{} parse errors", count);
+    }
+    anyhow::bail!("Parse error");
+}
@@ -1,8 +1,5 @@
 // In the style of: https://github.com/nst/JSONTestSuite
 
-use json_pop::lex::Token;
-use json_pop::parser::jsonParser as parser;
-use logos::Logos;
 use std::io::Read;
 
 fn main() -> anyhow::Result<()> {
@@ -16,8 +13,7 @@ fn main() -> anyhow::Result<()> {
     let mut buffer = String::new();
     let mut f = std::fs::File::open(path).expect("Unable to open file");
     f.read_to_string(&mut buffer)?;
-    let tokens = Token::lexer(&buffer).spanned().map(Token::to_lalr_triple);
-    let parsed = parser::new().parse(tokens);
+    let parsed = json_pop::parse_str(&buffer);
     match parsed {
         Err(_) => std::process::exit(1),
         _ => std::process::exit(0),
@@ -0,0 +1,36 @@
+//! The actions of the grammars, shared by `json.lalrpop` and
+//! `recovering.lalrpop` so that they differ only in what they do with errors:
+//! the former fails with them, the latter records them and carries on.
+use crate::error::CompilationError;
+use crate::escape;
+use crate::parser::ParseError;
+use crate::value::{Number, Value};
+use std::borrow::Cow;
+use std::ops::Range;
+
+/// Fails the parse with an error found by an action.
+pub(crate) fn fail<'a>(error: CompilationError) -> ParseError<'a> {
+    lalrpop_util::ParseError::User { error }
+}
+
+/// The value of the number token at `range`.
+pub(crate) fn number(literal: &str, range: Range<usize>) -> Result<Value<'_>, CompilationError> {
+    Number::new(literal)
+        .map(Value::Number)
+        .ok_or(CompilationError::NumericalError { range })
+}
+
+/// The error for the string token at `range`, which has no closing quote.
+pub(crate) fn unterminated(range: Range<usize>) -> CompilationError {
+    CompilationError::UnterminatedStringLiteral { range }
+}
+
+/// The string token starting at `start`, with its escapes decoded.
+pub(crate) fn string(literal: &str, start: usize) -> Result<Cow<'_, str>, CompilationError> {
+    escape::unescape(without_quotes(literal), start + 1)
+}
+
+/// The string token as written, without its quotes.
+pub(crate) fn without_quotes(literal: &str) -> &str {
+    &literal[1..literal.len() - 1]
+}
@@ -0,0 +1,186 @@
+//! The arrays and objects left open at a parse error, so an unexpected end
+//! of input or closing bracket can be shown along with the bracket it should
+//! have closed, see `extra::codespan`.
+//!
+//! The brackets are tracked as the tokens are given to the parser, by
+//! `parse_str_bracketed` and `parse_str_recovering`, and attached to their
+//! errors as `Bracketed`, rather than found again from the source.
+use crate::lex::Token;
+use crate::parser::ParseError;
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bracket {
+    /// `[` and `]` of an array.
+    Square,
+    /// `{` and `}` of an object.
+    Curly,
+}
+
+impl Bracket {
+    pub fn open(self) -> char {
+        match self {
+            Bracket::Square => '[',
+            Bracket::Curly => '{',
+        }
+    }
+
+    pub fn close(self) -> char {
+        match self {
+            Bracket::Square => ']',
+            Bracket::Curly => '}',
+        }
+    }
+}
+
+/// An opening bracket and its range in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Open {
+    pub bracket: Bracket,
+    pub range: Range<usize>,
+}
+
+/// A bracket given to the parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Seen {
+    Open(Open),
+    Close(Bracket, Range<usize>),
+}
+
+impl Seen {
+    fn start(&self) -> usize {
+        match self {
+            Seen::Open(open) => open.range.start,
+            Seen::Close(_, range) => range.start,
+        }
+    }
+}
+
+/// A parse error, with the brackets open where it was found.
+#[derive(Debug)]
+pub struct Bracketed<'a> {
+    pub error: ParseError<'a>,
+    /// The brackets opened before the error and not yet closed,
+    /// outermost first.
+    pub open: Vec<Open>,
+}
+
+impl<'a> Bracketed<'a> {
+    /// Attaches to each of `errors`, in order of their position, the brackets
+    /// open before it.  The brackets are those `seen` by the parser in order,
+    /// less any in the tokens at `dropped` by error recovery.
+    ///
+    /// Takes a single pass over the brackets.
+    pub(crate) fn attach(
+        seen: &[Seen],
+        dropped: &HashSet<usize>,
+        errors: Vec<ParseError<'a>>,
+    ) -> Vec<Bracketed<'a>> {
+        let mut open = Vec::new();
+        let mut seen = seen
+            .iter()
+            .filter(|seen| !dropped.contains(&seen.start()))
+            .peekable();
+        errors
+            .into_iter()
+            .map(|error| {
+                let position = crate::error::parse_error_range(&error).start;
+                while let Some(next) = seen.next_if(|seen| seen.start() < position) {
+                    match next {
+                        Seen::Open(bracket) => open.push(bracket.clone()),
+                        // The parser only accepts the bracket matching the
+                        // innermost open one, but recovery may have given up
+                        // on those opened within it.
+                        Seen::Close(bracket, _) => {
+                            while let Some(closed) = open.pop() {
+                                if closed.bracket == *bracket {
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+                Bracketed {
+                    error,
+                    open: open.clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// The bracket left open at an unexpected end of input,
+    /// or not matched by an unexpected closing bracket.
+    pub fn unmatched(&self) -> Option<Unmatched> {
+        use lalrpop_util::ParseError::*;
+
+        let open = self.open.last()?.clone();
+        match &self.error {
+            UnrecognizedEOF { location, .. } => Some(Unmatched::Unclosed {
+                open,
+                eof: *location,
+            }),
+            UnrecognizedToken {
+                token: (start, token, end),
+                ..
+            } => {
+                let close = match token {
+                    Token::RBrack => Bracket::Square,
+                    Token::RBrace => Bracket::Curly,
+                    _ => return None,
+                };
+                if open.bracket == close {
+                    None
+                } else {
+                    Some(Unmatched::Mismatched {
+                        open,
+                        close: *start..*end,
+                    })
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Unmatched {
+    /// The input ended while `open` was still open.
+    Unclosed { open: Open, eof: usize },
+    /// A closing bracket of the other kind than the innermost `open`.
+    Mismatched { open: Open, close: Range<usize> },
+}
+
+impl Unmatched {
+    pub fn open(&self) -> &Open {
+        match self {
+            Unmatched::Unclosed { open, .. } | Unmatched::Mismatched { open, .. } => open,
+        }
+    }
+}
+
+/// Describes the opening bracket, "this `{` was never closed".
+impl fmt::Display for Unmatched {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bracket = self.open().bracket;
+        match self {
+            Unmatched::Unclosed { .. } => {
+                write!(f, "this `{}` was never closed", bracket.open())
+            }
+            Unmatched::Mismatched { .. } => {
+                // Closed with the other kind of bracket.
+                let close = match bracket {
+                    Bracket::Square => Bracket::Curly,
+                    Bracket::Curly => Bracket::Square,
+                };
+                write!(
+                    f,
+                    "`{}` does not match `{}` opened here",
+                    close.close(),
+                    bracket.open()
+                )
+            }
+        }
+    }
+}
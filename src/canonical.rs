@@ -0,0 +1,161 @@
+//! Canonical JSON, as specified by RFC 8785,
+//! the JSON Canonicalization Scheme (JCS).
+//!
+//! Object members are sorted by the UTF-16 code units of their keys,
+//! numbers are written as ECMAScript writes doubles,
+//! strings use the fewest escapes possible, and there is no whitespace.
+//!
+//! JCS requires keys to be unique, objects with duplicate keys
+//! keep those members in their original order.
+//! Parse with `options::DuplicateKeys::Reject` to rule them out.
+use crate::escape;
+use crate::value::Value;
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+
+/// A number too large to be represented as a double,
+/// which has no canonical form.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberOutOfRange {
+    /// The number as it was written.
+    pub number: String,
+}
+
+impl fmt::Display for NumberOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "number {} is out of range of a double", self.number)
+    }
+}
+
+impl std::error::Error for NumberOutOfRange {}
+
+/// Formats a double as ECMAScript's `Number.prototype.toString` does.
+///
+/// Returns `None` for NaN and the infinities, which JSON can't represent.
+pub fn number_to_string(x: f64) -> Option<String> {
+    if !x.is_finite() {
+        return None;
+    }
+    if x == 0.0 {
+        // Including negative zero.
+        return Some("0".to_string());
+    }
+
+    // The number of digits in the shortest form which round trips,
+    // then those digits rounded exactly, since where two forms are equally
+    // close ECMAScript takes the even one and the shortest form may not.
+    let shortest = format!("{:e}", x.abs());
+    let precision = shortest.find('e').unwrap().saturating_sub(2);
+    let sci = format!("{:.*e}", precision, x.abs());
+    let (mantissa, exponent) = sci.split_at(sci.find('e').unwrap());
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let exponent: i32 = exponent[1..].parse().unwrap();
+
+    // In the terms of ECMA-262, the value is `digits × 10^(n - k)`.
+    let k = digits.len() as i32;
+    let n = exponent + 1;
+    let mut s = String::new();
+    if x < 0.0 {
+        s.push('-');
+    }
+    if k <= n && n <= 21 {
+        s.push_str(&digits);
+        s.push_str(&"0".repeat((n - k) as usize));
+    } else if 0 < n && n <= 21 {
+        s.push_str(&digits[..n as usize]);
+        s.push('.');
+        s.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        s.push_str("0.");
+        s.push_str(&"0".repeat(-n as usize));
+        s.push_str(&digits);
+    } else {
+        s.push_str(&digits[..1]);
+        if k > 1 {
+            s.push('.');
+            s.push_str(&digits[1..]);
+        }
+        write!(s, "e{}{}", if n > 0 { '+' } else { '-' }, (n - 1).abs()).unwrap();
+    }
+    Some(s)
+}
+
+/// The arrays and objects being written, with the elements which remain.
+enum Frame<'v, 'a> {
+    Array(std::slice::Iter<'v, Value<'a>>),
+    Object(std::vec::IntoIter<&'v (Cow<'a, str>, Value<'a>)>),
+}
+
+/// Writes `value` in its canonical form.
+pub fn canonicalize(value: &Value<'_>) -> Result<String, NumberOutOfRange> {
+    let mut out = String::new();
+    let mut stack = Vec::new();
+    let mut next = Some(value);
+    // Writing to a `String` can't fail.
+    loop {
+        if let Some(value) = next.take() {
+            match value {
+                Value::Number(number) => {
+                    let x = number.as_f64().unwrap_or(f64::INFINITY);
+                    match number_to_string(x) {
+                        Some(s) => out.push_str(&s),
+                        None => {
+                            return Err(NumberOutOfRange {
+                                number: number.to_string(),
+                            })
+                        }
+                    }
+                }
+                Value::String(string) => escape::write_escaped(&mut out, string, false).unwrap(),
+                Value::Bool(flag) => write!(out, "{}", flag).unwrap(),
+                Value::Null => out.push_str("null"),
+                Value::Array(array) => {
+                    out.push('[');
+                    stack.push((Frame::Array(array.iter()), true));
+                }
+                Value::Object(obj) => {
+                    let mut members: Vec<_> = obj.iter().collect();
+                    members.sort_by_cached_key(|(key, _)| key.encode_utf16().collect::<Vec<u16>>());
+                    out.push('{');
+                    stack.push((Frame::Object(members.into_iter()), true));
+                }
+            }
+        }
+
+        let (frame, first) = match stack.last_mut() {
+            Some(top) => top,
+            None => return Ok(out),
+        };
+        match frame {
+            Frame::Array(elements) => match elements.next() {
+                Some(value) => {
+                    if !*first {
+                        out.push(',');
+                    }
+                    next = Some(value);
+                }
+                None => {
+                    out.push(']');
+                    stack.pop();
+                    continue;
+                }
+            },
+            Frame::Object(members) => match members.next() {
+                Some((key, value)) => {
+                    if !*first {
+                        out.push(',');
+                    }
+                    escape::write_escaped(&mut out, key, false).unwrap();
+                    out.push(':');
+                    next = Some(value);
+                }
+                None => {
+                    out.push('}');
+                    stack.pop();
+                    continue;
+                }
+            },
+        }
+        *first = false;
+    }
+}
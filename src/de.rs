@@ -0,0 +1,388 @@
+#![cfg(feature = "serde")]
+//! Deserializing serde types from JSON text, by way of a `Value`.
+use crate::error::{ParseErrorDetails, Spanned};
+use crate::options::ParseOptions;
+use crate::path::{Path, PathSegment};
+use crate::value::Value;
+use serde::de::{self, IntoDeserializer as _};
+use std::borrow::Cow;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+/// Deserializes a `T` from JSON text.
+///
+/// Strings without escapes are borrowed from `source`,
+/// so fields such as `&'a str` can be deserialized without copying.
+pub fn from_str<'a, T: de::Deserialize<'a>>(source: &'a str) -> Result<T, Error> {
+    from_str_with(source, &ParseOptions::default())
+}
+
+/// Like `from_str`, but with the limits given in `options`.
+pub fn from_str_with<'a, T: de::Deserialize<'a>>(
+    source: &'a str,
+    options: &ParseOptions,
+) -> Result<T, Error> {
+    let value = crate::parse_str_with(source, options)
+        .map_err(|err| Error::Parse(ParseErrorDetails::with_source(&err, source)))?;
+    T::deserialize(Deserializer::new(value)).map_err(|err| err.located(source))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The source is not valid JSON.
+    Parse(ParseErrorDetails),
+    /// The JSON doesn't match the type being deserialized.
+    Data {
+        message: String,
+        /// The value which didn't match.
+        path: Path,
+        /// The range of the value at `path`, when the source is known.
+        range: Option<Range<usize>>,
+    },
+}
+
+impl Error {
+    /// Finds the range of the value at the path of a `Data` error in `source`.
+    pub fn located(mut self, source: &str) -> Error {
+        if let Error::Data { path, range, .. } = &mut self {
+            *range = path.locate(source);
+        }
+        self
+    }
+
+    /// Prepends `segment` to the path of a `Data` error,
+    /// as the error is returned from a nested value.
+    fn within(mut self, segment: PathSegment) -> Error {
+        if let Error::Data { path, .. } = &mut self {
+            path.segments.insert(0, segment);
+        }
+        self
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Data {
+            message: msg.to_string(),
+            path: Path::root(),
+            range: None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Parse(details) => write!(f, "{}", details),
+            Error::Data { message, path, .. } if path.segments.is_empty() => {
+                write!(f, "{}", message)
+            }
+            Error::Data { message, path, .. } => write!(f, "{} (at {})", message, path),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Spanned for Error {
+    fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Error::Parse(details) => Some(details.range.clone()),
+            Error::Data { range, .. } => range.clone(),
+        }
+    }
+}
+
+/// A `serde::Deserializer` which takes apart a `Value`.
+pub struct Deserializer<'de> {
+    value: Value<'de>,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(value: Value<'de>) -> Deserializer<'de> {
+        Deserializer { value }
+    }
+}
+
+fn visit_str<'de, V: de::Visitor<'de>>(
+    string: Cow<'de, str>,
+    visitor: V,
+) -> Result<V::Value, Error> {
+    match string {
+        Cow::Borrowed(string) => visitor.visit_borrowed_str(string),
+        Cow::Owned(string) => visitor.visit_string(string),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: de::Visitor<'de>>(mut self, visitor: V) -> Result<V::Value, Error> {
+        // `Value` implements `Drop`, so its contents are taken rather than moved out.
+        match &mut self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(flag) => visitor.visit_bool(*flag),
+            // Integers are visited as the smallest type they fit in,
+            // only other numbers are rounded to an `f64`.
+            Value::Number(number) if number.is_integer() => {
+                if let Some(n) = number.as_u64() {
+                    visitor.visit_u64(n)
+                } else if let Some(n) = number.as_i64() {
+                    visitor.visit_i64(n)
+                } else if let Some(n) = number.as_u128() {
+                    visitor.visit_u128(n)
+                } else if let Some(n) = number.as_i128() {
+                    visitor.visit_i128(n)
+                } else {
+                    Err(out_of_range(number, "a 128-bit integer"))
+                }
+            }
+            Value::Number(number) => {
+                visitor.visit_f64(number.as_f64().expect("a JSON number parses as an f64"))
+            }
+            Value::String(string) => visit_str(mem::take(string), visitor),
+            Value::Array(elements) => {
+                let mut seq = SeqAccess {
+                    elements: mem::take(elements).into_iter().enumerate(),
+                };
+                let result = visitor.visit_seq(&mut seq)?;
+                match seq.elements.len() {
+                    0 => Ok(result),
+                    remaining => Err(de::Error::invalid_length(
+                        seq.elements.next().unwrap().0 + remaining,
+                        &"fewer elements in array",
+                    )),
+                }
+            }
+            Value::Object(members) => {
+                let mut map = MapAccess {
+                    members: mem::take(members).into_iter(),
+                    next: None,
+                };
+                visitor.visit_map(&mut map)
+            }
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        mut self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match &mut self.value {
+            // A unit variant.
+            Value::String(variant) => visitor.visit_enum(mem::take(variant).into_deserializer()),
+            // Any other variant, as an object with the variant as its only key.
+            Value::Object(members) if members.len() == 1 => {
+                let (variant, value) = members.pop().unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(de::Error::invalid_type(
+                unexpected(&self.value),
+                &"a string or an object with a single key",
+            )),
+        }
+    }
+
+    fn deserialize_i128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self.value {
+            Value::Number(number) if number.is_integer() => match number.as_i128() {
+                Some(n) => visitor.visit_i128(n),
+                None => Err(out_of_range(number, "an i128")),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_u128<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match &self.value {
+            Value::Number(number) if number.is_integer() => match number.as_u128() {
+                Some(n) => visitor.visit_u128(n),
+                None => Err(out_of_range(number, "a u128")),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    // Any number, integers included, rounded to the nearest `f64`.
+    fn deserialize_f64<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value.as_f64() {
+            Some(x) => visitor.visit_f64(x),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_f64(visitor)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+fn out_of_range(number: &crate::value::Number<'_>, expected: &str) -> Error {
+    de::Error::invalid_value(
+        de::Unexpected::Other(&format!("integer {}", number)),
+        &expected,
+    )
+}
+
+fn unexpected<'v>(value: &'v Value<'_>) -> de::Unexpected<'v> {
+    match value {
+        Value::Null => de::Unexpected::Unit,
+        Value::Bool(flag) => de::Unexpected::Bool(*flag),
+        Value::Number(_) => de::Unexpected::Other("number"),
+        Value::String(string) => de::Unexpected::Str(string),
+        Value::Array(_) => de::Unexpected::Seq,
+        Value::Object(_) => de::Unexpected::Map,
+    }
+}
+
+struct SeqAccess<'de> {
+    elements: std::iter::Enumerate<std::vec::IntoIter<Value<'de>>>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        match self.elements.next() {
+            Some((index, value)) => seed
+                .deserialize(Deserializer::new(value))
+                .map(Some)
+                .map_err(|err| err.within(PathSegment::Index(index))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess<'de> {
+    members: std::vec::IntoIter<(Cow<'de, str>, Value<'de>)>,
+    /// The key and value of the member whose key was last deserialized.
+    next: Option<(Cow<'de, str>, Value<'de>)>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let (key, value) = match self.members.next() {
+            Some(member) => member,
+            None => return Ok(None),
+        };
+        let result = seed
+            .deserialize(Deserializer::new(Value::String(key.clone())))
+            .map_err(|err| err.within(PathSegment::Key(key.to_string())));
+        self.next = Some((key, value));
+        result.map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .next
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before its key"))?;
+        seed.deserialize(Deserializer::new(value))
+            .map_err(|err| err.within(PathSegment::Key(key.into_owned())))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.members.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: Cow<'de, str>,
+    value: Value<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = VariantAccess<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantAccess<'de>), Error> {
+        let key = self.variant.to_string();
+        let variant = seed.deserialize(Deserializer::new(Value::String(self.variant)))?;
+        Ok((
+            variant,
+            VariantAccess {
+                value: self.value,
+                key,
+            },
+        ))
+    }
+}
+
+struct VariantAccess<'de> {
+    value: Value<'de>,
+    key: String,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        let VariantAccess { value, key } = self;
+        de::Deserialize::deserialize(Deserializer::new(value))
+            .map_err(|err: Error| err.within(PathSegment::Key(key)))
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let VariantAccess { value, key } = self;
+        seed.deserialize(Deserializer::new(value))
+            .map_err(|err| err.within(PathSegment::Key(key)))
+    }
+
+    fn tuple_variant<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let VariantAccess { value, key } = self;
+        de::Deserializer::deserialize_seq(Deserializer::new(value), visitor)
+            .map_err(|err| err.within(PathSegment::Key(key)))
+    }
+
+    fn struct_variant<V: de::Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let VariantAccess { value, key } = self;
+        de::Deserializer::deserialize_map(Deserializer::new(value), visitor)
+            .map_err(|err| err.within(PathSegment::Key(key)))
+    }
+}
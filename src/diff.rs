@@ -0,0 +1,222 @@
+//! Structural differences between two values,
+//! such as a parsed config and the version it was expected to be.
+//!
+//! Values are compared rather than their text, so whitespace and the way
+//! strings and numbers are written don't matter, and each change is given
+//! with the `Path` of the value.  With both sources at hand `Path::locate`
+//! finds its range in each, see `extra::codespan::from_changes`.
+use crate::patch::{json_eq, members_by_key};
+use crate::path::{Path, PathSegment};
+use crate::value::Value;
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// Whether objects with the same members in a different order are equal,
+    /// rather than reported as `Change::Reordered`.
+    pub ignore_member_order: bool,
+}
+
+/// A difference between two values, at the path of the value within them.
+///
+/// The path is the same in both values, as arrays are only compared
+/// by position after the elements they start and end with in common.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    /// A member or element of the new value which isn't in the old one.
+    Added(Path),
+    /// A member or element of the old value which isn't in the new one.
+    Removed(Path),
+    /// A value replaced by a different one.
+    Changed(Path),
+    /// An object whose members are in a different order.
+    Reordered(Path),
+}
+
+impl Change {
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added(path)
+            | Change::Removed(path)
+            | Change::Changed(path)
+            | Change::Reordered(path) => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self {
+            Change::Added(_) => "added",
+            Change::Removed(_) => "removed",
+            Change::Changed(_) => "changed",
+            Change::Reordered(_) => "reordered",
+        };
+        let path = self.path();
+        if path.segments.is_empty() {
+            write!(f, "{} the top level value", what)
+        } else {
+            write!(f, "{} {}", what, path)
+        }
+    }
+}
+
+/// The changes which turn `old` into `new`.
+///
+/// Members of objects are matched by key, where an object has more than one
+/// member with a key the last is compared.  Arrays are matched after any
+/// elements they start and end with in common, so a single insertion
+/// or removal is found as such, and their remaining elements by position.
+pub fn compare(old: &Value<'_>, new: &Value<'_>, options: &DiffOptions) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let mut path = Path::root();
+    let mut steps = vec![Step::Compare(old, new)];
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::Compare(old, new) => {
+                let mut children = Vec::new();
+                compare_step(&mut children, options, &path, old, new);
+                steps.extend(children.into_iter().rev());
+            }
+            Step::Enter(segment) => path.push(segment),
+            Step::Leave => {
+                path.segments.pop();
+            }
+            Step::Push(change) => changes.push(change),
+        }
+    }
+    changes
+}
+
+/// Whether `compare` would find no changes.
+pub fn equal(old: &Value<'_>, new: &Value<'_>, options: &DiffOptions) -> bool {
+    let mut pairs = vec![(old, new)];
+    while let Some((old, new)) = pairs.pop() {
+        match (old, new) {
+            (Value::Object(_), Value::Object(_)) => {
+                let (old_members, new_members) = (members_by_key(old), members_by_key(new));
+                if old_members.len() != new_members.len()
+                    || (!options.ignore_member_order && key_order(old) != key_order(new))
+                {
+                    return false;
+                }
+                for (key, old) in old_members {
+                    match new_members.get(key) {
+                        Some(new) => pairs.push((old, new)),
+                        None => return false,
+                    }
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                if a.len() != b.len() {
+                    return false;
+                }
+                pairs.extend(a.iter().zip(b));
+            }
+            _ if !json_eq(old, new) => return false,
+            _ => (),
+        }
+    }
+    true
+}
+
+/// The keys of an object in the order they are first used.
+fn key_order<'v>(value: &'v Value<'_>) -> Vec<&'v str> {
+    let mut seen = HashSet::new();
+    value
+        .members()
+        .map(|(key, _)| key)
+        .filter(|key| seen.insert(*key))
+        .collect()
+}
+
+/// What remains to be done to compare two values, in the order of the changes.
+enum Step<'v> {
+    /// Compare two values at the current path.
+    Compare(&'v Value<'v>, &'v Value<'v>),
+    /// Move the current path into a member or element.
+    Enter(PathSegment),
+    /// Move the current path back out to its parent.
+    Leave,
+    Push(Change),
+}
+
+/// The steps which compare `old` with `new`, at `path`.
+fn compare_step<'v>(
+    steps: &mut Vec<Step<'v>>,
+    options: &DiffOptions,
+    path: &Path,
+    old: &'v Value<'v>,
+    new: &'v Value<'v>,
+) {
+    let child = |segment| {
+        let mut child = path.clone();
+        child.push(segment);
+        child
+    };
+    match (old, new) {
+        (Value::Object(_), Value::Object(_)) => {
+            let (old_keys, new_keys) = (key_order(old), key_order(new));
+            let (old_members, new_members) = (members_by_key(old), members_by_key(new));
+            if !options.ignore_member_order {
+                let old_order = old_keys.iter().filter(|key| new_members.contains_key(*key));
+                let new_order = new_keys.iter().filter(|key| old_members.contains_key(*key));
+                if !old_order.eq(new_order) {
+                    steps.push(Step::Push(Change::Reordered(path.clone())));
+                }
+            }
+            for key in &old_keys {
+                if !new_members.contains_key(key) {
+                    let segment = PathSegment::Key(key.to_string());
+                    steps.push(Step::Push(Change::Removed(child(segment))));
+                }
+            }
+            for key in &new_keys {
+                let segment = PathSegment::Key(key.to_string());
+                match old_members.get(key) {
+                    Some(old_value) => {
+                        steps.push(Step::Enter(segment));
+                        steps.push(Step::Compare(old_value, new_members[key]));
+                        steps.push(Step::Leave);
+                    }
+                    None => steps.push(Step::Push(Change::Added(child(segment)))),
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let prefix = a
+                .iter()
+                .zip(b)
+                .take_while(|(x, y)| equal(x, y, options))
+                .count();
+            let suffix = a[prefix..]
+                .iter()
+                .rev()
+                .zip(b[prefix..].iter().rev())
+                .take_while(|(x, y)| equal(x, y, options))
+                .count();
+            let a = &a[prefix..a.len() - suffix];
+            let b = &b[prefix..b.len() - suffix];
+            for (i, (x, y)) in a.iter().zip(b).enumerate() {
+                steps.push(Step::Enter(PathSegment::Index(prefix + i)));
+                steps.push(Step::Compare(x, y));
+                steps.push(Step::Leave);
+            }
+            let common = a.len().min(b.len());
+            for i in common..a.len() {
+                let segment = PathSegment::Index(prefix + i);
+                steps.push(Step::Push(Change::Removed(child(segment))));
+            }
+            for i in common..b.len() {
+                let segment = PathSegment::Index(prefix + i);
+                steps.push(Step::Push(Change::Added(child(segment))));
+            }
+        }
+        _ => {
+            if !json_eq(old, new) {
+                steps.push(Step::Push(Change::Changed(path.clone())));
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use crate::parser;
+use crate::position::{LineCol, LineIndex};
 use std::fmt;
 use std::ops::Range;
 
@@ -6,7 +7,9 @@ use std::ops::Range;
 /// bounded by the lifetime of the source string
 /// which was parsed.
 ///
-/// Due to these lifetimes, such a result cannot escape main.
+/// Due to these lifetimes, such a result cannot outlive the source.
+/// Use `value::Value::into_owned` and `TopLevelError` to return a parsed
+/// value and errors from functions which own their source.
 #[derive(Debug)]
 pub enum JsonPopError<'a> {
     Parse(crate::parser::ParseError<'a>),
@@ -17,22 +20,19 @@ pub enum JsonPopError<'a> {
 
 /// A top level error returned by processes or tests,
 /// unbounded by the lifetime of the string which was parsed.
-/// we should probably add error codes to these.
-///
-/// That will be a breaking change.
 #[derive(Debug)]
 pub enum TopLevelError {
     TestingError,
-    ParseError,
+    ParseError(ParseErrorDetails),
     Io(std::io::Error),
 }
 
 impl<'a> From<JsonPopError<'a>> for TopLevelError {
     fn from(it: JsonPopError<'a>) -> TopLevelError {
         match it {
-            JsonPopError::Parse(_) => {
+            JsonPopError::Parse(err) => {
                 // Convert to an error without the associated lifetimes.
-                TopLevelError::ParseError
+                TopLevelError::ParseError(ParseErrorDetails::new(&err))
             }
             JsonPopError::Io(err) => TopLevelError::Io(err),
             JsonPopError::TestError(_) => TopLevelError::TestingError,
@@ -40,27 +40,478 @@ impl<'a> From<JsonPopError<'a>> for TopLevelError {
     }
 }
 
+impl TopLevelError {
+    /// Like `From<JsonPopError>`, but also records the line and column
+    /// of parse errors within `source`.
+    pub fn from_source(err: JsonPopError<'_>, source: &str) -> TopLevelError {
+        match err {
+            JsonPopError::Parse(err) => {
+                TopLevelError::ParseError(ParseErrorDetails::with_source(&err, source))
+            }
+            err => TopLevelError::from(err),
+        }
+    }
+
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            TopLevelError::ParseError(details) => Some(details.code),
+            TopLevelError::TestingError | TopLevelError::Io(_) => None,
+        }
+    }
+}
+
+/// Everything known about a parse error, without borrowing from the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseErrorDetails {
+    pub code: ErrorCode,
+    /// The same message as the `Display` of the parse error.
+    pub message: String,
+    pub range: Range<usize>,
+    /// The start of `range`, when the source was available.
+    pub line_col: Option<LineCol>,
+    /// The tokens which would have been valid at `range`,
+    /// named as in the message, see `terminal_name`.
+    pub expected: Vec<String>,
+}
+
+impl ParseErrorDetails {
+    pub fn new(err: &parser::ParseError) -> ParseErrorDetails {
+        use lalrpop_util::ParseError::*;
+        let expected = match err {
+            UnrecognizedEOF { expected, .. } | UnrecognizedToken { expected, .. } => expected
+                .iter()
+                .filter_map(|terminal| terminal_name(terminal))
+                .map(str::to_string)
+                .collect(),
+            InvalidToken { .. } | ExtraToken { .. } | User { .. } => Vec::new(),
+        };
+        ParseErrorDetails {
+            code: ErrorCode::of_parse_error(err),
+            message: parse_error_message(err),
+            range: parse_error_range(err),
+            line_col: None,
+            expected,
+        }
+    }
+
+    pub fn with_source(err: &parser::ParseError, source: &str) -> ParseErrorDetails {
+        let mut details = ParseErrorDetails::new(err);
+        details.line_col = Some(LineIndex::new(source).line_col(details.range.start));
+        details
+    }
+}
+
+/// Stable codes for each kind of parse error, for reporting errors to
+/// programs rather than people.
+///
+/// Codes are never renumbered or reused, each is displayed as `JP` followed
+/// by its zero padded number, e.g. `JP0001`.
+///
+/// | Code   | Kind |
+/// |--------|------|
+/// | JP0001 | unterminated string literal |
+/// | JP0002 | invalid token |
+/// | JP0003 | invalid number |
+/// | JP0004 | invalid unicode escape |
+/// | JP0005 | unexpected end of input |
+/// | JP0006 | unexpected token |
+/// | JP0007 | extra token after the end of the value |
+/// | JP0008 | nesting depth limit exceeded |
+/// | JP0009 | input size limit exceeded |
+/// | JP0010 | string length limit exceeded |
+/// | JP0011 | array length limit exceeded |
+/// | JP0012 | object member limit exceeded |
+/// | JP0013 | value count limit exceeded |
+/// | JP0014 | duplicate object key |
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    UnterminatedString,
+    InvalidToken,
+    InvalidNumber,
+    InvalidUnicodeEscape,
+    UnexpectedEof,
+    UnexpectedToken,
+    ExtraToken,
+    DepthLimitExceeded,
+    InputTooLarge,
+    StringTooLong,
+    ArrayTooLong,
+    ObjectTooLarge,
+    TooManyValues,
+    DuplicateKey,
+}
+
+impl ErrorCode {
+    pub fn number(self) -> u16 {
+        match self {
+            ErrorCode::UnterminatedString => 1,
+            ErrorCode::InvalidToken => 2,
+            ErrorCode::InvalidNumber => 3,
+            ErrorCode::InvalidUnicodeEscape => 4,
+            ErrorCode::UnexpectedEof => 5,
+            ErrorCode::UnexpectedToken => 6,
+            ErrorCode::ExtraToken => 7,
+            ErrorCode::DepthLimitExceeded => 8,
+            ErrorCode::InputTooLarge => 9,
+            ErrorCode::StringTooLong => 10,
+            ErrorCode::ArrayTooLong => 11,
+            ErrorCode::ObjectTooLarge => 12,
+            ErrorCode::TooManyValues => 13,
+            ErrorCode::DuplicateKey => 14,
+        }
+    }
+
+    /// The code as it is displayed, e.g. `JP0001`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::UnterminatedString => "JP0001",
+            ErrorCode::InvalidToken => "JP0002",
+            ErrorCode::InvalidNumber => "JP0003",
+            ErrorCode::InvalidUnicodeEscape => "JP0004",
+            ErrorCode::UnexpectedEof => "JP0005",
+            ErrorCode::UnexpectedToken => "JP0006",
+            ErrorCode::ExtraToken => "JP0007",
+            ErrorCode::DepthLimitExceeded => "JP0008",
+            ErrorCode::InputTooLarge => "JP0009",
+            ErrorCode::StringTooLong => "JP0010",
+            ErrorCode::ArrayTooLong => "JP0011",
+            ErrorCode::ObjectTooLarge => "JP0012",
+            ErrorCode::TooManyValues => "JP0013",
+            ErrorCode::DuplicateKey => "JP0014",
+        }
+    }
+
+    pub fn of_parse_error(err: &parser::ParseError) -> ErrorCode {
+        use lalrpop_util::ParseError::*;
+        match err {
+            InvalidToken { .. } => ErrorCode::InvalidToken,
+            UnrecognizedEOF { .. } => ErrorCode::UnexpectedEof,
+            UnrecognizedToken { .. } => ErrorCode::UnexpectedToken,
+            ExtraToken { .. } => ErrorCode::ExtraToken,
+            User { error } => error.code(),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// This error lives inside the the parsers Error type.
 /// So it's a sub-error of a parse error.
 #[derive(Debug)]
 pub enum CompilationError {
-    LexicalError { range: Range<usize> },
-    NumericalError { range: Range<usize> },
-    UnterminatedStringLiteral { range: Range<usize> },
+    LexicalError {
+        range: Range<usize>,
+    },
+    NumericalError {
+        range: Range<usize>,
+    },
+    UnterminatedStringLiteral {
+        range: Range<usize>,
+    },
+    /// A `\uXXXX` escape which is a lone surrogate,
+    /// or a high surrogate which isn't followed by a low surrogate.
+    InvalidUnicodeEscape {
+        range: Range<usize>,
+    },
+    /// Arrays and objects were nested deeper than `ParseOptions::max_depth`.
+    DepthLimitExceeded {
+        range: Range<usize>,
+    },
+    /// The input was larger than `ParseOptions::max_input_size`,
+    /// the range is the part of the input past the limit.
+    InputTooLarge {
+        range: Range<usize>,
+    },
+    /// A string was longer than `ParseOptions::max_string_length`.
+    StringTooLong {
+        range: Range<usize>,
+    },
+    /// An array had more elements than `ParseOptions::max_array_length`,
+    /// the range is the first element past the limit.
+    ArrayTooLong {
+        range: Range<usize>,
+    },
+    /// An object had more members than `ParseOptions::max_object_members`,
+    /// the range is the key of the first member past the limit.
+    ObjectTooLarge {
+        range: Range<usize>,
+    },
+    /// The document had more values than `ParseOptions::max_values`,
+    /// the range is the first value past the limit.
+    TooManyValues {
+        range: Range<usize>,
+    },
+    /// An object contained the same key twice,
+    /// rejected by `ParseOptions::duplicate_keys`.
+    DuplicateKey {
+        first: Range<usize>,
+        second: Range<usize>,
+    },
+}
+
+impl CompilationError {
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            CompilationError::LexicalError { range }
+            | CompilationError::NumericalError { range }
+            | CompilationError::UnterminatedStringLiteral { range }
+            | CompilationError::InvalidUnicodeEscape { range }
+            | CompilationError::DepthLimitExceeded { range }
+            | CompilationError::InputTooLarge { range }
+            | CompilationError::StringTooLong { range }
+            | CompilationError::ArrayTooLong { range }
+            | CompilationError::ObjectTooLarge { range }
+            | CompilationError::TooManyValues { range } => range.clone(),
+            CompilationError::DuplicateKey { second, .. } => second.clone(),
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            CompilationError::LexicalError { .. } => ErrorCode::InvalidToken,
+            CompilationError::NumericalError { .. } => ErrorCode::InvalidNumber,
+            CompilationError::UnterminatedStringLiteral { .. } => ErrorCode::UnterminatedString,
+            CompilationError::InvalidUnicodeEscape { .. } => ErrorCode::InvalidUnicodeEscape,
+            CompilationError::DepthLimitExceeded { .. } => ErrorCode::DepthLimitExceeded,
+            CompilationError::InputTooLarge { .. } => ErrorCode::InputTooLarge,
+            CompilationError::StringTooLong { .. } => ErrorCode::StringTooLong,
+            CompilationError::ArrayTooLong { .. } => ErrorCode::ArrayTooLong,
+            CompilationError::ObjectTooLarge { .. } => ErrorCode::ObjectTooLarge,
+            CompilationError::TooManyValues { .. } => ErrorCode::TooManyValues,
+            CompilationError::DuplicateKey { .. } => ErrorCode::DuplicateKey,
+        }
+    }
+}
+
+/// Errors which can point at the range of the source they occurred in.
+///
+/// Ranges are byte offsets, a `LineIndex` of the source maps them to lines and columns.
+pub trait Spanned {
+    fn span(&self) -> Option<Range<usize>>;
+
+    /// The line and column at which the error starts.
+    fn line_col(&self, index: &LineIndex) -> Option<LineCol> {
+        self.span().map(|range| index.line_col(range.start))
+    }
+}
+
+impl Spanned for CompilationError {
+    fn span(&self) -> Option<Range<usize>> {
+        Some(self.range())
+    }
+}
+
+impl<'a> Spanned for parser::ParseError<'a> {
+    fn span(&self) -> Option<Range<usize>> {
+        Some(parse_error_range(self))
+    }
+}
+
+impl<'a> Spanned for JsonPopError<'a> {
+    fn span(&self) -> Option<Range<usize>> {
+        match self {
+            JsonPopError::Parse(err) => err.span(),
+            JsonPopError::Io(_) | JsonPopError::TestError(_) => None,
+        }
+    }
+}
+
+impl Spanned for TopLevelError {
+    fn span(&self) -> Option<Range<usize>> {
+        match self {
+            TopLevelError::ParseError(details) => Some(details.range.clone()),
+            TopLevelError::TestingError | TopLevelError::Io(_) => None,
+        }
+    }
+}
+
+/// The range of the source a parse error occurred at.
+pub fn parse_error_range(err: &parser::ParseError) -> Range<usize> {
+    use lalrpop_util::ParseError::*;
+    match err {
+        InvalidToken { location } | UnrecognizedEOF { location, .. } => *location..*location,
+        UnrecognizedToken {
+            token: (start, _, end),
+            ..
+        }
+        | ExtraToken {
+            token: (start, _, end),
+        } => *start..*end,
+        User { error } => error.range(),
+    }
+}
+
+/// A message describing a parse error, without its location.
+///
+/// This is the wording used both by `Display` and the pretty diagnostics.
+pub fn parse_error_message(err: &parser::ParseError) -> String {
+    use lalrpop_util::ParseError::*;
+    match err {
+        InvalidToken { .. } => "invalid token".to_string(),
+        UnrecognizedEOF { expected, .. } if expected.is_empty() => {
+            "unexpected end of input".to_string()
+        }
+        UnrecognizedEOF { expected, .. } => format!(
+            "unexpected end of input, expected {}",
+            join_expected(expected)
+        ),
+        UnrecognizedToken {
+            token: (_, token, _),
+            expected,
+        } if expected.is_empty() => format!("expected the end of input, found {}", token),
+        UnrecognizedToken {
+            token: (_, token, _),
+            expected,
+        } => format!("expected {}, found {}", join_expected(expected), token),
+        ExtraToken {
+            token: (_, token, _),
+        } => {
+            format!("found {} after the end of the value", token)
+        }
+        User { error } => error.to_string(),
+    }
+}
+
+/// Describes the terminals LALRPOP lists as expected,
+/// "a comma", "a comma or closing bracket", "a value".
+pub fn join_expected(expected: &[String]) -> String {
+    let mut names: Vec<&str> = expected
+        .iter()
+        .filter_map(|terminal| terminal_name(terminal))
+        .collect();
+    // Anything which starts a value is listed as one.
+    if VALUE_STARTS.iter().all(|name| names.contains(name)) {
+        names.retain(|name| !VALUE_STARTS.contains(name));
+        names.insert(0, "value");
+    }
+    let list = match names.split_last() {
+        None => return "nothing".to_string(),
+        Some((last, [])) => last.to_string(),
+        Some((last, rest)) => format!("{} or {}", rest.join(", "), last),
+    };
+    // A single article for the whole list, none for keywords.
+    if list.starts_with('`') {
+        list
+    } else if list.starts_with(|c| "aeiou".contains(c)) {
+        format!("an {}", list)
+    } else {
+        format!("a {}", list)
+    }
+}
+
+const VALUE_STARTS: [&str; 7] = [
+    "opening bracket",
+    "opening brace",
+    "`true`",
+    "`false`",
+    "`null`",
+    "number",
+    "string",
+];
+
+/// The name of a terminal from LALRPOP's expected tokens, as `Token`s are
+/// named when displayed.  `None` for terminals of the grammar which are
+/// only there to report errors, such as `missing_quote`.
+pub fn terminal_name(terminal: &str) -> Option<&str> {
+    match terminal {
+        "\",\"" => Some("comma"),
+        "\":\"" => Some("colon"),
+        "\"[\"" => Some("opening bracket"),
+        "\"]\"" => Some("closing bracket"),
+        "\"{\"" => Some("opening brace"),
+        "\"}\"" => Some("closing brace"),
+        "\"true\"" => Some("`true`"),
+        "\"false\"" => Some("`false`"),
+        "\"null\"" => Some("`null`"),
+        "missing_quote" | "invalid" => None,
+        terminal => Some(terminal),
+    }
+}
+
+/// Displays an error followed by the line and column it occurred at,
+/// "unterminated string literal at line 3, column 14".
+pub struct Located<'e, 's, E> {
+    pub error: &'e E,
+    pub index: &'e LineIndex<'s>,
+}
+
+impl<'e, 's, E: fmt::Display + Spanned> fmt::Display for Located<'e, 's, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        match self.error.line_col(self.index) {
+            Some(line_col) => write!(f, " at {}", line_col),
+            None => Ok(()),
+        }
+    }
 }
 
 impl fmt::Display for CompilationError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:#?}", self)
+        match self {
+            CompilationError::LexicalError { .. } => write!(f, "invalid token"),
+            CompilationError::NumericalError { .. } => write!(f, "invalid number"),
+            CompilationError::UnterminatedStringLiteral { .. } => {
+                write!(f, "unterminated string literal")
+            }
+            CompilationError::InvalidUnicodeEscape { .. } => {
+                write!(f, "invalid unicode escape, unpaired surrogate")
+            }
+            CompilationError::DepthLimitExceeded { .. } => {
+                write!(f, "arrays and objects are nested too deeply")
+            }
+            CompilationError::InputTooLarge { .. } => write!(f, "input is too large"),
+            CompilationError::StringTooLong { .. } => write!(f, "string is too long"),
+            CompilationError::ArrayTooLong { .. } => write!(f, "array has too many elements"),
+            CompilationError::ObjectTooLarge { .. } => write!(f, "object has too many members"),
+            CompilationError::TooManyValues { .. } => write!(f, "document has too many values"),
+            CompilationError::DuplicateKey { .. } => write!(f, "duplicate key"),
+        }
     }
 }
 
+impl std::error::Error for CompilationError {}
+
 impl<'a> fmt::Display for JsonPopError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:#?}", self)
+        match self {
+            JsonPopError::Parse(err) => write!(f, "{}", parse_error_message(err)),
+            JsonPopError::Io(err) => write!(f, "{}", err),
+            JsonPopError::TestError(err) => write!(f, "{:#?}", err),
+        }
+    }
+}
+
+/// `Display` gives the message of the error wrapped, so it has no `source`,
+/// which would repeat it when the chain of errors is reported.
+impl<'a> std::error::Error for JsonPopError<'a> {}
+
+impl fmt::Display for TopLevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopLevelError::TestingError => write!(f, "test failed"),
+            TopLevelError::ParseError(details) => write!(f, "{}", details),
+            TopLevelError::Io(err) => write!(f, "{}", err),
+        }
     }
 }
 
+impl fmt::Display for ParseErrorDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)?;
+        match self.line_col {
+            Some(line_col) => write!(f, " at {}", line_col),
+            None => write!(f, " at bytes {}..{}", self.range.start, self.range.end),
+        }
+    }
+}
+
+/// Like `JsonPopError`, without a `source`.
+impl std::error::Error for TopLevelError {}
+
 impl<'a> From<std::io::Error> for JsonPopError<'a> {
     fn from(err: std::io::Error) -> Self {
         JsonPopError::Io(err)
@@ -72,3 +523,9 @@ impl<'a> From<parser::ParseError<'a>> for JsonPopError<'a> {
         JsonPopError::Parse(err)
     }
 }
+
+impl<'a> From<crate::brackets::Bracketed<'a>> for JsonPopError<'a> {
+    fn from(err: crate::brackets::Bracketed<'a>) -> Self {
+        JsonPopError::Parse(err.error)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::error::CompilationError;
+use std::borrow::Cow;
+use std::fmt;
+
+/// Decodes the escape sequences of a string literal's contents,
+/// the text between the quotes.
+///
+/// `offset` is the position of `s` within the source, and is only used
+/// to give errors a range in the source.
+///
+/// When `s` contains no escapes it is borrowed, otherwise a new string is allocated.
+pub fn unescape(s: &str, offset: usize) -> Result<Cow<'_, str>, CompilationError> {
+    let first = match s.find('\\') {
+        Some(first) => first,
+        None => return Ok(Cow::Borrowed(s)),
+    };
+
+    let bytes = s.as_bytes();
+    let mut unescaped = String::with_capacity(s.len());
+    // The start of the text which has yet to be copied.
+    let mut start = 0;
+    let mut i = first;
+    while i < bytes.len() {
+        if bytes[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+
+        unescaped.push_str(&s[start..i]);
+        let (c, len) = match bytes.get(i + 1) {
+            Some(b'"') => ('"', 2),
+            Some(b'\\') => ('\\', 2),
+            Some(b'/') => ('/', 2),
+            Some(b'b') => ('\u{8}', 2),
+            Some(b'f') => ('\u{c}', 2),
+            Some(b'n') => ('\n', 2),
+            Some(b'r') => ('\r', 2),
+            Some(b't') => ('\t', 2),
+            Some(b'u') => unescape_unicode(s, i, offset)?,
+            // The lexer only produces valid escape sequences,
+            // so this is only reachable when called with arbitrary strings.
+            _ => {
+                return Err(CompilationError::LexicalError {
+                    range: offset + i..offset + (i + 2).min(bytes.len()),
+                })
+            }
+        };
+        unescaped.push(c);
+        i += len;
+        start = i;
+    }
+    unescaped.push_str(&s[start..]);
+    Ok(Cow::Owned(unescaped))
+}
+
+/// Decodes the `\uXXXX` escape starting at `i`, along with the low surrogate
+/// following it if it is a high surrogate.
+///
+/// Returns the character and the length of the escape sequence(s).
+fn unescape_unicode(s: &str, i: usize, offset: usize) -> Result<(char, usize), CompilationError> {
+    let invalid = |len: usize| CompilationError::InvalidUnicodeEscape {
+        range: offset + i..offset + (i + len).min(s.len()),
+    };
+
+    let high = hex4(s, i + 2).ok_or_else(|| invalid(6))?;
+    match high {
+        0xD800..=0xDBFF => {
+            if s.get(i + 6..i + 8) != Some("\\u") {
+                return Err(invalid(6));
+            }
+            let low = hex4(s, i + 8).ok_or_else(|| invalid(12))?;
+            if let 0xDC00..=0xDFFF = low {
+                let code_point = 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00);
+                std::char::from_u32(code_point)
+                    .map(|c| (c, 12))
+                    .ok_or_else(|| invalid(12))
+            } else {
+                Err(invalid(12))
+            }
+        }
+        0xDC00..=0xDFFF => Err(invalid(6)),
+        _ => std::char::from_u32(high)
+            .map(|c| (c, 6))
+            .ok_or_else(|| invalid(6)),
+    }
+}
+
+fn hex4(s: &str, i: usize) -> Option<u32> {
+    let digits = s.get(i..i + 4)?;
+    if digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        u32::from_str_radix(digits, 16).ok()
+    } else {
+        None
+    }
+}
+
+/// Writes `s` as a quoted string literal, escaping it as required by RFC 8259.
+///
+/// Control characters without a short escape are written as lowercase `\u00xx`.
+/// When `ascii_only` is set, every non-ASCII character is escaped as `\uXXXX`,
+/// using surrogate pairs for characters outside the basic multilingual plane.
+pub fn write_escaped<W: fmt::Write>(w: &mut W, s: &str, ascii_only: bool) -> fmt::Result {
+    w.write_char('"')?;
+    // The start of the text which has yet to be written.
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let short = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\u{8}' => "\\b",
+            '\u{c}' => "\\f",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\u{0}'..='\u{1f}' => "",
+            _ if ascii_only && !c.is_ascii() => "",
+            _ => continue,
+        };
+        w.write_str(&s[start..i])?;
+        if short.is_empty() {
+            for unit in c.encode_utf16(&mut [0; 2]) {
+                write!(w, "\\u{:04x}", unit)?;
+            }
+        } else {
+            w.write_str(short)?;
+        }
+        start = i + c.len_utf8();
+    }
+    w.write_str(&s[start..])?;
+    w.write_char('"')
+}
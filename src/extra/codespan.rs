@@ -1,83 +1,281 @@
 #![cfg(feature = "pretty_errors")]
-use crate::error::CompilationError;
-use crate::parser::ParseError;
+use crate::brackets::Bracketed;
+use crate::diff::Change;
+use crate::error::{self, CompilationError};
+use crate::fix::{self, Suggestion};
 use crate::value;
-use codespan_reporting::diagnostic::{Diagnostic, Label};
+use codespan_reporting::diagnostic::{Diagnostic, Label, Severity};
 use codespan_reporting::files::SimpleFiles;
 use codespan_reporting::term;
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use std::fmt;
 
 pub fn from_parse_error<'a, T: AsRef<str> + 'a>(
     filename: &'a str,
     data: &'a T,
-    error: &ParseError<'a>,
+    error: &Bracketed<'a>,
 ) -> (SimpleFiles<&'a str, &'a str>, Diagnostic<usize>) {
-    use lalrpop_util::ParseError::*;
+    let mut files = SimpleFiles::new();
+    let diagnostic = add_parse_error(&mut files, filename, data.as_ref(), error);
+    (files, diagnostic)
+}
 
+/// Diagnostics for each of the errors found by `parse_str_recovering`.
+pub fn from_parse_errors<'a, T: AsRef<str> + 'a>(
+    filename: &'a str,
+    data: &'a T,
+    errors: &[Bracketed<'a>],
+) -> (SimpleFiles<&'a str, &'a str>, Vec<Diagnostic<usize>>) {
     let mut files = SimpleFiles::new();
-    let file_id = files.add(filename, data.as_ref());
-    let join_expected = |expected: &Vec<String>| -> String {
-        if let Some((caboose, rest)) = expected.split_last() {
-            if rest.is_empty() {
-                format!("Expected: {}", &caboose)
-            } else {
-                format!("Expected: {} or {}", rest.join(", "), &caboose)
-            }
+    let diagnostics = add_parse_errors(&mut files, filename, data.as_ref(), errors);
+    (files, diagnostics)
+}
+
+/// Adds `source` to the caller's `files` as `filename`, giving the diagnostic
+/// for `error` in it.  Diagnostics for any number of files can be gathered
+/// this way and emitted together with `files`.
+pub fn add_parse_error<N: fmt::Display + Clone, S: AsRef<str>>(
+    files: &mut SimpleFiles<N, S>,
+    filename: N,
+    source: S,
+    error: &Bracketed<'_>,
+) -> Diagnostic<usize> {
+    let file_id = files.add(filename, source);
+    let source = files.get(file_id).unwrap().source().as_ref();
+    parse_error_diagnostic(file_id, source, error)
+}
+
+/// Like `add_parse_error`, for each of the errors found by `parse_str_recovering`.
+pub fn add_parse_errors<N: fmt::Display + Clone, S: AsRef<str>>(
+    files: &mut SimpleFiles<N, S>,
+    filename: N,
+    source: S,
+    errors: &[Bracketed<'_>],
+) -> Vec<Diagnostic<usize>> {
+    let file_id = files.add(filename, source);
+    let source = files.get(file_id).unwrap().source().as_ref();
+    errors
+        .iter()
+        .map(|error| parse_error_diagnostic(file_id, source, error))
+        .collect()
+}
+
+/// Counts of the files checked and the diagnostics reported for them,
+/// "3 errors in 2 of 10 files".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub files: usize,
+    /// The files with at least one error.
+    pub failed: usize,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl Summary {
+    /// Counts a file, with the diagnostics reported for it.
+    pub fn add_file(&mut self, diagnostics: &[Diagnostic<usize>]) {
+        let count = |severities: &[Severity]| {
+            diagnostics
+                .iter()
+                .filter(|diagnostic| severities.contains(&diagnostic.severity))
+                .count()
+        };
+        let errors = count(&[Severity::Bug, Severity::Error]);
+        self.files += 1;
+        self.failed += (errors > 0) as usize;
+        self.errors += errors;
+        self.warnings += count(&[Severity::Warning]);
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {}", what),
+            n => format!("{} {}s", n, what),
+        };
+        if self.errors == 0 {
+            write!(f, "no errors")?;
         } else {
-            // If this error occurs we need to test for it at the caller.
-            "Had great expectations?".to_string()
+            write!(f, "{}", plural(self.errors, "error"))?;
         }
-    };
+        if self.warnings > 0 {
+            write!(f, " and {}", plural(self.warnings, "warning"))?;
+        }
+        if self.failed > 0 {
+            write!(f, " in {} of {}", self.failed, plural(self.files, "file"))
+        } else {
+            write!(f, " in {}", plural(self.files, "file"))
+        }
+    }
+}
 
-    let diag = match error {
+/// The diagnostic for `error`, labelling the bracket left open at an
+/// unexpected end of input or closing bracket, and with a help note
+/// for the fix when `fix::suggest` has one.
+fn parse_error_diagnostic(
+    file_id: usize,
+    source: &str,
+    bracketed: &Bracketed<'_>,
+) -> Diagnostic<usize> {
+    use lalrpop_util::ParseError::*;
+
+    let error = &bracketed.error;
+    let message = error::parse_error_message(error);
+    let mut diagnostic = match error {
         InvalidToken { location } => Diagnostic::error()
-            .with_message("Invalid token")
+            .with_message(message)
             .with_labels(vec![Label::primary(file_id, *location..*location)]),
-        UnrecognizedEOF { location, expected } => Diagnostic::error()
-            .with_message("Unexpected EOF")
-            .with_labels(vec![
-                Label::primary(file_id, *location..*location).with_message(join_expected(expected))
-            ]),
+        UnrecognizedEOF { location, .. } => Diagnostic::error()
+            .with_message(message)
+            .with_labels(vec![Label::primary(file_id, *location..*location)
+                .with_message("unexpected end of input")]),
         UnrecognizedToken {
-            token: (start, _tok, end),
-            expected,
+            token: (start, tok, end),
+            ..
         } => Diagnostic::error()
-            .with_message("Unrecognized token")
+            .with_message(message)
             .with_labels(vec![
-                Label::primary(file_id, *start..*end).with_message(join_expected(expected))
+                Label::primary(file_id, *start..*end).with_message(format!("found {}", tok))
             ]),
         ExtraToken {
             token: (start, _tok, end),
         } => Diagnostic::error()
-            .with_message("Extra token")
-            .with_labels(vec![Label::primary(file_id, *start..*end)])
-            .with_message("Extra token"),
-        User { error } => {
-            let range = match error {
-                CompilationError::LexicalError { range }
-                | CompilationError::NumericalError { range }
-                | CompilationError::UnterminatedStringLiteral { range } => range,
-            };
+            .with_message(message)
+            .with_labels(vec![Label::primary(file_id, *start..*end)]),
+        User {
+            error: CompilationError::DuplicateKey { first, second },
+        } => Diagnostic::error().with_message(message).with_labels(vec![
+            Label::primary(file_id, second.clone()).with_message("duplicate key"),
+            Label::secondary(file_id, first.clone()).with_message("first used here"),
+        ]),
+        User { error } => Diagnostic::error()
+            .with_message(message)
+            .with_labels(vec![Label::primary(file_id, error.range())]),
+    };
+    if let Some(unmatched) = bracketed.unmatched() {
+        diagnostic.labels.push(
+            Label::secondary(file_id, unmatched.open().range.clone())
+                .with_message(unmatched.to_string()),
+        );
+    }
+    match fix::suggest(source, bracketed) {
+        Some(suggestion) => diagnostic.with_notes(vec![help(&suggestion)]),
+        None => diagnostic,
+    }
+}
+
+/// "help: remove the trailing comma", followed by the replacement
+/// when there is one.
+fn help(suggestion: &Suggestion) -> String {
+    if suggestion.replacement.is_empty() {
+        format!("help: {}", suggestion.message)
+    } else {
+        format!("help: {}: `{}`", suggestion.message, suggestion.replacement)
+    }
+}
+
+/// A diagnostic for an error deserializing with `de::from_str`,
+/// pointing at the value which failed to deserialize.
+#[cfg(feature = "serde")]
+pub fn from_de_error<'a, T: AsRef<str> + 'a>(
+    filename: &'a str,
+    data: &'a T,
+    error: &crate::de::Error,
+) -> (SimpleFiles<&'a str, &'a str>, Diagnostic<usize>) {
+    use crate::de::Error;
 
-            Diagnostic::error()
-                .with_message(format!("{:?}", error))
-                .with_labels(vec![Label::primary(file_id, range.clone())])
+    let mut files = SimpleFiles::new();
+    let file_id = files.add(filename, data.as_ref());
+    let diag = match error {
+        Error::Parse(details) => Diagnostic::error()
+            .with_message(&details.message)
+            .with_labels(vec![Label::primary(file_id, details.range.clone())]),
+        Error::Data {
+            message,
+            path,
+            range,
+        } => {
+            let at = if path.segments.is_empty() {
+                "at the top level value".to_string()
+            } else {
+                format!("at {}", path)
+            };
+            let diag = Diagnostic::error().with_message(message);
+            match range {
+                Some(range) => {
+                    diag.with_labels(vec![Label::primary(file_id, range.clone()).with_message(at)])
+                }
+                None => diag.with_notes(vec![at]),
+            }
         }
     };
     (files, diag)
 }
 
+/// Emits the diagnostic for a parse error to stderr, as being in `filename`.
 pub fn maybe_show_error<'a>(
+    filename: &str,
     _source: &str,
-    parsed: Result<value::Value<'a>, crate::parser::ParseError<'a>>,
+    parsed: Result<value::Value<'a>, Bracketed<'a>>,
 ) -> Result<value::Value<'a>, crate::error::JsonPopError<'a>> {
-    if let Err(error) = parsed {
-        let writer = StandardStream::stderr(ColorChoice::Auto);
-        let config = codespan_reporting::term::Config::default();
-        let (files, diagnostic) = from_parse_error("stdin", &_source, &error);
-        term::emit(&mut writer.lock(), &config, &files, &diagnostic)?;
-        Err(crate::error::JsonPopError::Parse(error))
-    } else {
-        parsed.map_err(crate::error::JsonPopError::Parse)
+    match parsed {
+        Ok(value) => Ok(value),
+        Err(error) => {
+            let writer = StandardStream::stderr(ColorChoice::Auto);
+            let config = codespan_reporting::term::Config::default();
+            let (files, diagnostic) = from_parse_error(filename, &_source, &error);
+            term::emit(&mut writer.lock(), &config, &files, &diagnostic)?;
+            Err(crate::error::JsonPopError::Parse(error.error))
+        }
     }
 }
+
+/// Diagnostics for the changes between two documents found by `diff::compare`,
+/// labelling the value in the old document, the new one, or both.
+pub fn from_changes<'a>(
+    (old_name, old): (&'a str, &'a str),
+    (new_name, new): (&'a str, &'a str),
+    changes: &[Change],
+) -> (SimpleFiles<&'a str, &'a str>, Vec<Diagnostic<usize>>) {
+    let mut files = SimpleFiles::new();
+    let old_id = files.add(old_name, old);
+    let new_id = files.add(new_name, new);
+    let diagnostics = changes
+        .iter()
+        .map(|change| {
+            let path = change.path();
+            let (in_old, in_new) = match change {
+                Change::Added(_) => (None, Some("added here")),
+                Change::Removed(_) => (Some("removed from here"), None),
+                Change::Changed(_) => (Some("was this"), Some("now this")),
+                Change::Reordered(_) => (
+                    Some("members were in this order"),
+                    Some("now in this order"),
+                ),
+            };
+            let mut labels = Vec::new();
+            let mut notes = Vec::new();
+            for (file_id, source, message) in [(old_id, old, in_old), (new_id, new, in_new)].iter()
+            {
+                if let Some(message) = message {
+                    match path.locate(source) {
+                        Some(range) => {
+                            labels.push(Label::primary(*file_id, range).with_message(*message))
+                        }
+                        None => notes.push(format!(
+                            "{}: {}",
+                            files.get(*file_id).unwrap().name(),
+                            message
+                        )),
+                    }
+                }
+            }
+            Diagnostic::note()
+                .with_message(change.to_string())
+                .with_labels(labels)
+                .with_notes(notes)
+        })
+        .collect();
+    (files, diagnostics)
+}
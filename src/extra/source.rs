@@ -1,11 +1,34 @@
+use crate::brackets::Bracketed;
 #[cfg(feature = "pretty_errors")]
 use crate::extra::codespan;
-use crate::{error, lex, parser, value};
-
-use logos::Logos as _;
+use crate::options::ParseOptions;
+use crate::{error, value};
 
 #[derive(Debug)]
-pub struct Source<'a>(&'a str);
+pub struct Source<'a> {
+    text: &'a str,
+    options: ParseOptions,
+    name: &'a str,
+}
+
+impl<'a> Source<'a> {
+    pub fn with_options(self, options: ParseOptions) -> Source<'a> {
+        Source { options, ..self }
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// The file name errors are reported in, `"stdin"` unless given.
+    pub fn with_name(self, name: &'a str) -> Source<'a> {
+        Source { name, ..self }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+}
 
 #[derive(Debug)]
 /// Parsed includes a source context which presumably implements some
@@ -13,7 +36,7 @@ pub struct Source<'a>(&'a str);
 /// and a result from parsing.
 pub struct Parsed<'a, T> {
     pub source_ctxt: &'a T,
-    pub parse_result: parser::ParseResult<'a>,
+    pub parse_result: Result<value::Value<'a>, Bracketed<'a>>,
 }
 
 pub trait Parsable<'a> {
@@ -25,12 +48,9 @@ pub trait Parsable<'a> {
 impl<'a> Parsable<'a> for Source<'a> {
     type SourceContext = Self;
     fn parse(&'a self) -> Parsed<'a, Self> {
-        let lexer = lex::Token::lexer(self.0)
-            .spanned()
-            .map(lex::Token::to_lalr_triple);
         Parsed {
             source_ctxt: &self,
-            parse_result: parser::jsonParser::new().parse(lexer),
+            parse_result: crate::parse_str_bracketed(self.text, &self.options),
         }
     }
 
@@ -48,13 +68,22 @@ impl<'a> ErrorHandling<'a> for Parsed<'a, Source<'a>> {
         use cfg_if::cfg_if;
         cfg_if! {
             if #[cfg(feature = "pretty_errors")] {
-                codespan::maybe_show_error(self.source_ctxt.as_ref(), self.parse_result)
+                codespan::maybe_show_error(
+                    self.source_ctxt.name(),
+                    self.source_ctxt.as_ref(),
+                    self.parse_result,
+                )
             } else {
               use std::io::Write;
-              if self.parse_result.is_err() == false {
-                  write!(std::io::stderr(), "{:#?}", self.source_ctxt)?;
+              match self.parse_result {
+                  Ok(value) => Ok(value),
+                  Err(error) => {
+                      let error = error::JsonPopError::from(error);
+                      let index = crate::position::LineIndex::new(self.source_ctxt.as_ref());
+                      writeln!(std::io::stderr(), "{}", error::Located { error: &error, index: &index })?;
+                      Err(error)
+                  }
               }
-              Ok(self.parse_result?)
            }
         }
     }
@@ -62,12 +91,16 @@ impl<'a> ErrorHandling<'a> for Parsed<'a, Source<'a>> {
 
 impl<'a, T: AsRef<str> + 'a> From<&'a T> for Source<'a> {
     fn from(it: &'a T) -> Source<'a> {
-        Source(it.as_ref())
+        Source {
+            text: it.as_ref(),
+            options: ParseOptions::default(),
+            name: "stdin",
+        }
     }
 }
 
 impl<'a> AsRef<str> for Source<'a> {
     fn as_ref(&self) -> &str {
-        &self.0
+        self.text
     }
 }
@@ -1,7 +1,5 @@
 #[cfg(test)]
-use crate::{error, extra::source, extra::source::Parsable as _, parser, value};
-#[cfg(test)]
-use logos::Logos as _;
+use crate::{error, extra::source, extra::source::Parsable as _, value};
 
 #[cfg(not(test))]
 use crate::extra::never;
@@ -39,15 +37,10 @@ impl<'a> source::Parsable<'a> for Test<'a> {
     type SourceContext = Test<'a>;
     fn parse(&'a self) -> source::Parsed<'a, Self> {
         match self {
-            Test::TestValid(src) | Test::TestInvalid(src) => {
-                let lexer = crate::lex::Token::lexer(src.as_ref())
-                    .spanned()
-                    .map(crate::lex::Token::to_lalr_triple);
-                source::Parsed {
-                    source_ctxt: &self,
-                    parse_result: parser::jsonParser::new().parse(lexer),
-                }
-            }
+            Test::TestValid(src) | Test::TestInvalid(src) => source::Parsed {
+                source_ctxt: &self,
+                parse_result: crate::parse_str_bracketed(src.as_ref(), src.options()),
+            },
         }
     }
 
@@ -73,7 +66,7 @@ impl<'a> source::ErrorHandling<'a> for source::Parsed<'a, Test<'a>> {
                     self.source_ctxt.source(),
                 ),
             )),
-            (_, _) => Ok(self.parse_result.map_err(error::JsonPopError::Parse)?),
+            (_, _) => Ok(self.parse_result.map_err(error::JsonPopError::from)?),
         }
     }
 
@@ -83,7 +76,7 @@ impl<'a> source::ErrorHandling<'a> for source::Parsed<'a, Test<'a>> {
             let mut writer = codespan_reporting::term::termcolor::Buffer::no_color();
             let config = codespan_reporting::term::Config::default();
             let (files, diagnostic) = crate::extra::codespan::from_parse_error(
-                "stdin",
+                self.source_ctxt.source().name(),
                 self.source_ctxt.source(),
                 &error,
             );
@@ -93,7 +86,7 @@ impl<'a> source::ErrorHandling<'a> for source::Parsed<'a, Test<'a>> {
             if self.source_ctxt.should_fail() {
                 Ok(value::Value::Null)
             } else {
-                Err(crate::error::JsonPopError::Parse(error))
+                Err(crate::error::JsonPopError::from(error))
             }
         } else {
             if self.source_ctxt.should_fail() {
@@ -104,7 +97,7 @@ impl<'a> source::ErrorHandling<'a> for source::Parsed<'a, Test<'a>> {
                     ),
                 ));
             }
-            self.parse_result.map_err(crate::error::JsonPopError::Parse)
+            self.parse_result.map_err(crate::error::JsonPopError::from)
         }
     }
 }
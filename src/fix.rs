@@ -0,0 +1,268 @@
+//! Suggested fixes for common mistakes in hand written JSON,
+//! trailing and missing commas, single quoted strings, unquoted keys,
+//! and arrays or objects which aren't closed or are closed with
+//! the wrong bracket.
+//!
+//! Each `Suggestion` is a replacement of a range of the source, so can be
+//! shown alongside the error, see `extra::codespan`, or applied with `fix`.
+use crate::brackets::{Bracket, Bracketed, Open, Unmatched};
+use crate::error::CompilationError;
+use crate::lex::Token;
+use crate::options::ParseOptions;
+use std::ops::Range;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// The range of the source to replace, empty when inserting.
+    pub range: Range<usize>,
+    pub replacement: String,
+    /// What the replacement does, "remove the trailing comma".
+    pub message: String,
+}
+
+impl Suggestion {
+    fn new(range: Range<usize>, replacement: impl Into<String>, message: &str) -> Suggestion {
+        Suggestion {
+            range,
+            replacement: replacement.into(),
+            message: message.to_string(),
+        }
+    }
+
+    /// The source with the replacement made.
+    pub fn apply(&self, source: &str) -> String {
+        let mut fixed = String::with_capacity(source.len() + self.replacement.len());
+        fixed.push_str(&source[..self.range.start]);
+        fixed.push_str(&self.replacement);
+        fixed.push_str(&source[self.range.end..]);
+        fixed
+    }
+}
+
+/// A fix for `error`, when it is one of the mistakes recognized.
+pub fn suggest(source: &str, error: &Bracketed<'_>) -> Option<Suggestion> {
+    use lalrpop_util::ParseError::*;
+
+    match &error.error {
+        UnrecognizedEOF { location, expected } => {
+            close_brackets(source, &error.open, *location, expected)
+        }
+        UnrecognizedToken {
+            token: (start, token, _),
+            expected,
+        } => match token {
+            Token::RBrack | Token::RBrace => {
+                trailing_comma(source, *start).or_else(|| mismatched_bracket(error))
+            }
+            Token::String(_)
+            | Token::MissingEndQuote(_)
+            | Token::Number(_)
+            | Token::True
+            | Token::False
+            | Token::Null
+            | Token::LBrack
+            | Token::LBrace
+                // `,` is also listed after a whole value, where nothing may follow.
+                if expects(expected, ",") && !error.open.is_empty() =>
+            {
+                let end = previous_end(source, *start);
+                // Between a key and its value.
+                if expects(expected, ":") {
+                    Some(Suggestion::new(end..end, ":", "insert a colon"))
+                } else {
+                    Some(Suggestion::new(end..end, ",", "insert a comma"))
+                }
+            }
+            _ => None,
+        }
+        .or_else(|| unquoted_key(source, *start)),
+        User {
+            error: CompilationError::LexicalError { range },
+        } => single_quoted(source, range.start).or_else(|| unquoted_key(source, range.start)),
+        _ => None,
+    }
+}
+
+/// Applies suggestions to `source` until it parses, or fails with errors
+/// for which there are none.  Gives the source as fixed, and the
+/// suggestions applied, each to the source as fixed by the rounds before.
+///
+/// Each round parses the source once, recovering from errors, and applies
+/// the suggestions for all of them which don't overlap.  Another round is
+/// only needed where recovering from one error skipped over another,
+/// as with several missing commas in a row.
+pub fn fix(source: &str) -> (String, Vec<Suggestion>) {
+    let mut fixed = source.to_string();
+    let mut applied = Vec::new();
+    let options = ParseOptions::default();
+    // A fix could uncover an earlier error, guard against fixes undoing
+    // each other.
+    for _ in 0..=source.len() {
+        let mut suggestions: Vec<_> = crate::parse_str_recovering(&fixed, &options)
+            .errors
+            .iter()
+            .filter_map(|error| suggest(&fixed, error))
+            .collect();
+        suggestions.sort_by_key(|suggestion| (suggestion.range.start, suggestion.range.end));
+        let mut round = String::with_capacity(fixed.len());
+        let mut end = 0;
+        let before = applied.len();
+        for suggestion in suggestions {
+            // Overlapping, or inserting at the same place as, the last.
+            if applied.len() > before && suggestion.range.start <= end {
+                continue;
+            }
+            round.push_str(&fixed[end..suggestion.range.start]);
+            round.push_str(&suggestion.replacement);
+            end = suggestion.range.end;
+            applied.push(suggestion);
+        }
+        if applied.len() == before {
+            break;
+        }
+        round.push_str(&fixed[end..]);
+        fixed = round;
+    }
+    (fixed, applied)
+}
+
+fn expects(expected: &[String], terminal: &str) -> bool {
+    expected
+        .iter()
+        .any(|name| name.len() > 1 && name.starts_with('"') && &name[1..name.len() - 1] == terminal)
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+/// The end of the token before `position`, skipping whitespace.
+fn previous_end(source: &str, position: usize) -> usize {
+    source[..position].trim_end_matches(is_whitespace).len()
+}
+
+fn previous_char(source: &str, position: usize) -> Option<(usize, char)> {
+    source[..previous_end(source, position)]
+        .char_indices()
+        .last()
+}
+
+/// A comma before the `]` or `}` at `position`.
+fn trailing_comma(source: &str, position: usize) -> Option<Suggestion> {
+    match previous_char(source, position) {
+        Some((comma, ',')) => Some(Suggestion::new(
+            comma..comma + 1,
+            "",
+            "remove the trailing comma",
+        )),
+        _ => None,
+    }
+}
+
+/// Replaces a closing bracket with the one matching the innermost open bracket.
+fn mismatched_bracket(error: &Bracketed<'_>) -> Option<Suggestion> {
+    match error.unmatched()? {
+        Unmatched::Mismatched { open, close } => {
+            let message = match open.bracket {
+                Bracket::Square => "close the array",
+                Bracket::Curly => "close the object",
+            };
+            Some(Suggestion::new(
+                close,
+                open.bracket.close().to_string(),
+                message,
+            ))
+        }
+        Unmatched::Unclosed { .. } => None,
+    }
+}
+
+/// Closes the arrays and objects still open at the end of the input,
+/// instead of a trailing comma if there is one.
+fn close_brackets(
+    source: &str,
+    open: &[Open],
+    end: usize,
+    expected: &[String],
+) -> Option<Suggestion> {
+    let open: Vec<_> = open.iter().map(|open| open.bracket).collect();
+    let innermost = *open.last()?;
+    let closers: String = open.iter().rev().map(|bracket| bracket.close()).collect();
+    let message = match &open[..] {
+        [Bracket::Square] => "close the array",
+        [Bracket::Curly] => "close the object",
+        _ => "close the arrays and objects left open",
+    };
+    match previous_char(source, end) {
+        Some((comma, ',')) => Some(Suggestion::new(comma..end, closers, message)),
+        _ if expects(expected, &innermost.close().to_string()) => {
+            Some(Suggestion::new(end..end, closers, message))
+        }
+        _ => None,
+    }
+}
+
+/// A string quoted with `'`, starting at `position`, as a JSON string.
+fn single_quoted(source: &str, position: usize) -> Option<Suggestion> {
+    let rest = source[position..].strip_prefix('\'')?;
+    let mut replacement = String::from("\"");
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' => {
+                replacement.push('"');
+                return Some(Suggestion::new(
+                    position..position + i + 2,
+                    replacement,
+                    "use double quotes for strings",
+                ));
+            }
+            '"' => replacement.push_str("\\\""),
+            '\\' => match chars.next() {
+                Some((_, '\'')) => replacement.push('\''),
+                Some((_, c)) => {
+                    replacement.push('\\');
+                    replacement.push(c);
+                }
+                None => return None,
+            },
+            '\n' => return None,
+            c => replacement.push(c),
+        }
+    }
+    None
+}
+
+/// An identifier starting at `position` used as the key of a member,
+/// after a comma or the start of the object, or after a value where the
+/// comma is missing.
+fn unquoted_key(source: &str, position: usize) -> Option<Suggestion> {
+    let rest = &source[position..];
+    let len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(rest.len());
+    let key = &rest[..len];
+    let is_key = key.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && rest[len..]
+            .trim_start_matches(is_whitespace)
+            .starts_with(':');
+    if !is_key {
+        return None;
+    }
+    match previous_char(source, position)? {
+        (_, '{') | (_, ',') => Some(Suggestion::new(
+            position..position + len,
+            format!("\"{}\"", key),
+            "quote the key",
+        )),
+        (at, c) if c == '"' || c == ']' || c == '}' || c.is_ascii_alphanumeric() => {
+            let end = at + c.len_utf8();
+            Some(Suggestion::new(
+                end..position + len,
+                format!(",{}\"{}\"", &source[end..position], key),
+                "insert a comma and quote the key",
+            ))
+        }
+        _ => None,
+    }
+}
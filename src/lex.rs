@@ -62,8 +62,24 @@ impl<'a> Token<'a> {
     }
 }
 
+/// How tokens are named in error messages, "a comma",
+/// "string literal \"foo\"".
 impl<'a> fmt::Display for Token<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:#?}", self)
+        match self {
+            Token::Error => write!(f, "an invalid token"),
+            Token::True => write!(f, "`true`"),
+            Token::False => write!(f, "`false`"),
+            Token::Null => write!(f, "`null`"),
+            Token::Colon => write!(f, "a colon"),
+            Token::Comma => write!(f, "a comma"),
+            Token::LBrace => write!(f, "an opening brace"),
+            Token::RBrace => write!(f, "a closing brace"),
+            Token::LBrack => write!(f, "an opening bracket"),
+            Token::RBrack => write!(f, "a closing bracket"),
+            Token::Number(text) => write!(f, "number {}", text),
+            Token::String(text) => write!(f, "string literal {}", text),
+            Token::MissingEndQuote(text) => write!(f, "unterminated string literal {}", text),
+        }
     }
 }
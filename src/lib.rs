@@ -1,84 +1,136 @@
+mod actions;
+pub mod brackets;
+pub mod canonical;
+pub mod de;
+pub mod diff;
 pub mod error;
+pub mod escape;
 pub mod extra;
+pub mod fix;
 pub mod lex;
+pub mod options;
+pub mod patch;
+pub mod path;
+pub mod pointer;
+pub mod position;
+pub mod recover;
+pub mod ser;
+pub mod value;
 
 use crate::error::CompilationError;
 use crate::lex::Token;
 
 pub use lalrpop_util;
-use logos::Logos;
+
+#[cfg(feature = "serde")]
+pub use de::{from_str, from_str_with};
+#[cfg(feature = "serde")]
+pub use ser::{to_string, to_string_pretty, to_writer, to_writer_pretty};
 
 pub mod parser {
     #![allow(clippy::all)]
     use lalrpop_util::lalrpop_mod;
     lalrpop_mod!(pub json);
+    lalrpop_mod!(pub recovering);
     use super::*;
     pub use json::*;
 
     pub type ParseError<'a> = lalrpop_util::ParseError<usize, Token<'a>, CompilationError>;
+    pub type ErrorRecovery<'a> = lalrpop_util::ErrorRecovery<usize, Token<'a>, CompilationError>;
     pub type ParseResult<'a> = Result<value::Value<'a>, ParseError<'a>>;
 }
 
-pub mod value {
-    use lexical;
-    use std::fmt;
-
-    #[derive(Debug, Clone, PartialEq)]
-    pub enum Value<'a> {
-        Number(f64),
-        String(&'a str),
-        Object(Vec<(&'a str, Value<'a>)>),
-        Bool(bool),
-        Null,
-        Array(Vec<Value<'a>>),
-    }
-
-    impl<'a> fmt::Display for Value<'a> {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            match self {
-                Value::Number(float) => write!(f, "{}", lexical::to_string(*float)),
-                Value::String(string) => write!(f, "\"{}\"", string),
-                Value::Object(obj) => {
-                    write!(f, "{{")?;
-                    if let Some(((key, value), rest)) = obj.split_first() {
-                        write!(f, "\"{}\": {}", key, value)?;
-                        for (key, value) in rest.iter() {
-                            write!(f, ", \"{}\": {}", key, value)?
-                        }
-                    }
-                    write!(f, "}}")
-                }
-                Value::Bool(flag) => write!(f, "{}", flag),
-                Value::Null => write!(f, "null"),
-                Value::Array(array) => {
-                    write!(f, "[")?;
-                    if let Some((value, rest)) = array.split_first() {
-                        write!(f, "{}", value)?;
-                        for value in rest.iter() {
-                            write!(f, ", {}", value)?
-                        }
-                    }
-                    write!(f, "]")
-                }
-            }
-        }
-    }
-}
-
+/// Parses a value, without any of the limits of `options::ParseOptions`.
+///
+/// Arrays and objects are nested to any depth, use `parse_str_with`
+/// and its default options to limit them to 128 levels.
 pub fn parse_str<'a>(
     bytes: &'a str,
 ) -> std::result::Result<
     value::Value<'a>,
     lalrpop_util::ParseError<usize, Token<'a>, CompilationError>,
 > {
-    let lexer = Token::lexer(bytes).spanned().map(Token::to_lalr_triple);
-    parser::jsonParser::new().parse(lexer)
+    let options = options::ParseOptions {
+        max_depth: None,
+        ..Default::default()
+    };
+    parse_str_with(bytes, &options)
+}
+
+/// Like `parse_str`, but with the limits given in `options`.
+pub fn parse_str_with<'a>(
+    bytes: &'a str,
+    options: &options::ParseOptions,
+) -> parser::ParseResult<'a> {
+    parser::jsonParser::new().parse(options, options::tokens(bytes, options))
+}
+
+/// Like `parse_str_with`, but an error comes with the brackets open where
+/// it was found, as needed by `fix::suggest` and `extra::codespan`.
+pub fn parse_str_bracketed<'a>(
+    bytes: &'a str,
+    options: &options::ParseOptions,
+) -> Result<value::Value<'a>, brackets::Bracketed<'a>> {
+    let mut tokens = options::Checked::new(bytes, options);
+    parser::jsonParser::new()
+        .parse(options, &mut tokens)
+        .map_err(|error| {
+            let mut errors =
+                brackets::Bracketed::attach(&tokens.brackets, &Default::default(), vec![error]);
+            errors.pop().unwrap()
+        })
+}
+
+/// Like `parse_str_bracketed`, but continues past errors to find all of them,
+/// giving a partial value with placeholders where there were errors.
+pub fn parse_str_recovering<'a>(
+    bytes: &'a str,
+    options: &options::ParseOptions,
+) -> recover::Recovered<'a> {
+    let mut recovery = recover::Recovery::default();
+    let mut tokens = options::Checked::new(bytes, options);
+    let limit = std::cell::Cell::new(None);
+    let result = parser::recovering::jsonParser::new().parse(
+        options,
+        &mut recovery,
+        recover::tokens(&mut tokens, &limit),
+    );
+    recover::Recovered::new(result, recovery, limit.take(), &tokens.brackets)
 }
 
 pub fn stringify<'a, W: std::io::Write>(w: &mut W, v: &'a value::Value<'a>) -> std::io::Result<()> {
     write!(w, "{}", *v)
 }
 
+/// Like `stringify`, but writes according to the given `ser::Config`.
+pub fn stringify_with<'a, W: std::io::Write>(
+    w: &mut W,
+    v: &'a value::Value<'a>,
+    config: &ser::Config,
+) -> std::io::Result<()> {
+    write!(w, "{}", ser::Formatted { value: v, config })
+}
+
+/// Like `stringify`, but indented over multiple lines as by `ser::Config::pretty`.
+pub fn stringify_pretty<'a, W: std::io::Write>(
+    w: &mut W,
+    v: &'a value::Value<'a>,
+) -> std::io::Result<()> {
+    stringify_with(w, v, &ser::Config::pretty())
+}
+
+/// Like `stringify`, but in the canonical form of RFC 8785, see `canonical`.
+///
+/// Fails with `InvalidData` if a number is out of range of a double.
+pub fn stringify_canonical<'a, W: std::io::Write>(
+    w: &mut W,
+    v: &'a value::Value<'a>,
+) -> std::io::Result<()> {
+    let text = canonical::canonicalize(v)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+    w.write_all(text.as_bytes())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -107,8 +159,8 @@ mod test {
         use crate::value::Value;
         let sources = [
             ("[]", Value::Array([].to_vec())),
-            (r#""foo bar""#, Value::String("foo bar")),
-            (r#""""#, Value::String("")),
+            (r#""foo bar""#, Value::String("foo bar".into())),
+            (r#""""#, Value::String("".into())),
         ];
         let tests = sources
             .iter()
@@ -122,4 +174,1558 @@ mod test {
             );
         })
     }
+
+    #[test]
+    fn test_unescape() {
+        use crate::value::Value;
+        use std::borrow::Cow;
+
+        let escaped = parse_str(r#""a\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00""#).unwrap();
+        assert_eq!(escaped, Value::String("a\"\\/\u{8}\u{c}\n\r\té😀".into()));
+
+        match &parse_str(r#"{"plain": "é"}"#).unwrap() {
+            Value::Object(members) => match &members[..] {
+                [(Cow::Borrowed("plain"), Value::String(Cow::Borrowed("é")))] => (),
+                _ => panic!("expected borrowed strings: {:?}", members),
+            },
+            v => panic!("expected an object: {:?}", v),
+        }
+    }
+
+    #[test]
+    fn test_invalid_surrogates() {
+        use lalrpop_util::ParseError::User;
+        for (src, expected) in &[
+            (r#""\ud83d""#, 1..7),
+            (r#""\ude00\ud83d""#, 1..7),
+            (r#""x\ud83d\u0041""#, 2..14),
+        ] {
+            match parse_str(src) {
+                Err(User {
+                    error: CompilationError::InvalidUnicodeEscape { range },
+                }) => assert_eq!(&range, expected),
+                result => panic!("{}: {:?}", src, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stringify_round_trip() {
+        use crate::value::Value;
+
+        let values = [
+            Value::String("quote \" backslash \\ slash /".into()),
+            Value::String("\u{0}\u{8}\u{c}\n\r\t\u{1f}\u{7f}".into()),
+            Value::String("é ∀ 😀".into()),
+            Value::Object(vec![
+                (
+                    "key \"with\" quotes".into(),
+                    Value::Number(value::Number::new("1.5").unwrap()),
+                ),
+                (
+                    "😀\n".into(),
+                    Value::Array(vec![Value::Null, Value::Bool(true)]),
+                ),
+            ]),
+        ];
+        for ascii_only in &[false, true] {
+            let config = ser::Config {
+                ascii_only: *ascii_only,
+                ..ser::Config::default()
+            };
+            for value in values.iter() {
+                let mut buf = Vec::new();
+                stringify_with(&mut buf, value, &config).unwrap();
+                let text = String::from_utf8(buf).unwrap();
+                assert!(!ascii_only || text.is_ascii(), "{}", text);
+                assert_eq!(parse_str(&text).unwrap(), *value, "{}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_stringify_escapes() {
+        use crate::value::Value;
+
+        let value = Value::String("\"\\\u{1}é😀".into());
+        assert_eq!(value.to_string(), r#""\"\\\u0001é😀""#);
+        let mut buf = Vec::new();
+        let config = ser::Config {
+            ascii_only: true,
+            ..ser::Config::default()
+        };
+        stringify_with(&mut buf, &value, &config).unwrap();
+        assert_eq!(buf, br#""\"\\\u0001\u00e9\ud83d\ude00""#);
+    }
+
+    #[test]
+    fn test_pretty() {
+        use crate::ser::{Config, Indent, Newline};
+
+        let value = parse_str(r#"{"a": [1, {}, []], "b": {"c": null}}"#).unwrap();
+        let write = |config: &Config| {
+            let mut buf = Vec::new();
+            stringify_with(&mut buf, &value, config).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        let mut buf = Vec::new();
+        stringify_pretty(&mut buf, &value).unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\n  \"a\": [\n    1,\n    {},\n    []\n  ],\n  \"b\": {\n    \"c\": null\n  }\n}\n"
+        );
+        assert_eq!(
+            write(&Config {
+                indent: Some(Indent::Tab),
+                space_after_colon: false,
+                newline: Newline::CrLf,
+                trailing_newline: false,
+                ..Config::default()
+            }),
+            "{\r\n\t\"a\":[\r\n\t\t1,\r\n\t\t{},\r\n\t\t[]\r\n\t],\r\n\t\"b\":{\r\n\t\t\"c\":null\r\n\t}\r\n}"
+        );
+        assert_eq!(
+            write(&Config {
+                space_after_colon: false,
+                space_after_comma: false,
+                ..Config::default()
+            }),
+            r#"{"a":[1,{},[]],"b":{"c":null}}"#
+        );
+        assert_eq!(
+            write(&Config {
+                space_after_colon: false,
+                ..Config::default()
+            }),
+            r#"{"a":[1, {}, []], "b":{"c":null}}"#
+        );
+        assert_eq!(
+            write(&Config {
+                space_after_comma: false,
+                ..Config::default()
+            }),
+            r#"{"a": [1,{},[]],"b": {"c": null}}"#
+        );
+        assert_eq!(write(&Config::default()), value.to_string());
+        assert_eq!(parse_str(&write(&Config::pretty())).unwrap(), value);
+    }
+
+    // The examples of RFC 8785 sections 3.2.2 and 3.2.3.
+    #[test]
+    fn test_canonical() {
+        use crate::canonical::canonicalize;
+
+        let src = r#"{
+  "numbers": [333333333.33333329, 1E30, 4.50,
+              2e-3, 0.000000000000000000000000001],
+  "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+  "literals": [null, true, false]
+}"#;
+        assert_eq!(
+            canonicalize(&parse_str(src).unwrap()).unwrap(),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        let src = r#"{
+  "\u20ac": "Euro Sign",
+  "\r": "Carriage Return",
+  "\ufb33": "Hebrew Letter Dalet With Dagesh",
+  "1": "One",
+  "\ud83d\ude00": "Emoji: Grinning Face",
+  "\u0080": "Control",
+  "\u00f6": "Latin Small Letter O With Diaeresis"
+}"#;
+        let canonical = canonicalize(&parse_str(src).unwrap()).unwrap();
+        match &parse_str(&canonical).unwrap() {
+            value::Value::Object(members) => assert_eq!(
+                members
+                    .iter()
+                    .map(|(_, value)| value.to_string())
+                    .collect::<Vec<_>>(),
+                [
+                    r#""Carriage Return""#,
+                    r#""One""#,
+                    r#""Control""#,
+                    r#""Latin Small Letter O With Diaeresis""#,
+                    r#""Euro Sign""#,
+                    r#""Emoji: Grinning Face""#,
+                    r#""Hebrew Letter Dalet With Dagesh""#,
+                ]
+            ),
+            value => panic!("{:?}", value),
+        }
+
+        assert!(canonicalize(&parse_str("[1e400]").unwrap()).is_err());
+        assert_eq!(
+            canonicalize(&parse_str("[-0, 1E2, -1.50]").unwrap()).unwrap(),
+            "[0,100,-1.5]"
+        );
+    }
+
+    // The number formatting examples of RFC 8785 appendix B.
+    #[test]
+    fn test_canonical_numbers() {
+        use crate::canonical::number_to_string;
+
+        let examples = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for (bits, expected) in examples.iter() {
+            let x = f64::from_bits(*bits);
+            assert_eq!(
+                number_to_string(x).as_deref(),
+                Some(*expected),
+                "{:x}",
+                bits
+            );
+        }
+        assert_eq!(number_to_string(f64::from_bits(0x7fffffffffffffff)), None);
+        assert_eq!(number_to_string(f64::from_bits(0x7ff0000000000000)), None);
+    }
+
+    #[test]
+    fn test_lossless_numbers() {
+        use crate::value::Value;
+
+        let src = "[12345678901234567890, 0.1000000000000000055511151231257827, -170141183460469231731687303715884105728, 1e400]";
+        let value = parse_str(src).unwrap();
+        let numbers = match &value {
+            Value::Array(numbers) => numbers
+                .iter()
+                .map(|n| match n {
+                    Value::Number(n) => n,
+                    _ => panic!("expected a number: {:?}", n),
+                })
+                .collect::<Vec<_>>(),
+            _ => panic!("expected an array: {:?}", value),
+        };
+
+        assert_eq!(numbers[0].as_u64(), Some(12345678901234567890));
+        assert_eq!(numbers[0].as_i64(), None);
+        assert_eq!(numbers[0].as_i128(), Some(12345678901234567890));
+        assert_eq!(numbers[1].as_str(), "0.1000000000000000055511151231257827");
+        assert_eq!(numbers[1].as_i64(), None);
+        assert_eq!(numbers[1].as_f64(), Some(0.1));
+        assert_eq!(numbers[2].as_i128(), Some(i128::MIN));
+        assert_eq!(numbers[3].as_f64(), Some(f64::INFINITY));
+
+        let mut buf = Vec::new();
+        stringify(&mut buf, &value).unwrap();
+        assert_eq!(std::str::from_utf8(&buf).unwrap(), src);
+    }
+
+    #[test]
+    fn test_number_syntax() {
+        use crate::value::Number;
+
+        for valid in &["0", "-0", "10", "1.5", "-1.5e10", "1E+2", "0e-0"] {
+            assert!(Number::new(*valid).is_some(), "{}", valid);
+        }
+        for invalid in &["", "-", "01", "1.", ".5", "1e", "1e+", "+1", "1.5.5", "NaN"] {
+            assert!(Number::new(*invalid).is_none(), "{}", invalid);
+        }
+        assert_eq!(Number::from(u64::MAX).as_u64(), Some(u64::MAX));
+        assert!(Number::from_f64(f64::NAN).is_none());
+    }
+
+    #[test]
+    fn test_owned_value() -> Result<(), error::TopLevelError> {
+        use crate::value::{OwnedValue, Value};
+
+        fn read(source: String) -> Result<OwnedValue, error::TopLevelError> {
+            let value = parse_str(&source).map_err(error::JsonPopError::from)?;
+            Ok(value.into_owned())
+        }
+
+        let owned = read(r#"{"a\tb": ["c", 1.5, null]}"#.to_string())?;
+        let owned = std::thread::spawn(move || owned).join().unwrap();
+        let borrowed = owned.as_borrowed();
+        assert_eq!(borrowed, owned);
+        assert_eq!(
+            borrowed,
+            parse_str(r#"{"a\tb": ["c", 1.5, null]}"#).unwrap()
+        );
+        match &borrowed {
+            Value::Object(members) => {
+                assert!(matches!(members[0].0, std::borrow::Cow::Borrowed(_)))
+            }
+            _ => panic!("expected an object"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_path_locate() {
+        use crate::path::{Path, PathSegment::*};
+
+        let src = r#"{"a": [1, {"b": "x", "\u0063": [true]}], "d": null}"#;
+        let locate = |segments: Vec<_>| Path { segments }.locate(src).map(|range| &src[range]);
+        assert_eq!(locate(vec![]), Some(src));
+        assert_eq!(locate(vec![Key("a".into()), Index(0)]), Some("1"));
+        assert_eq!(
+            locate(vec![Key("a".into()), Index(1), Key("c".into())]),
+            Some("[true]")
+        );
+        assert_eq!(
+            locate(vec![Key("a".into()), Index(1), Key("c".into()), Index(0)]),
+            Some("true")
+        );
+        assert_eq!(locate(vec![Key("a".into()), Index(2)]), None);
+        assert_eq!(locate(vec![Key("b".into())]), None);
+        assert_eq!(locate(vec![Index(0)]), None);
+        let src = r#"{"a": 1, "b": 2, "a": [3], "c": 4}"#;
+        let locate = |segments: Vec<_>| Path { segments }.locate(src).map(|range| &src[range]);
+        assert_eq!(locate(vec![Key("a".into())]), Some("[3]"));
+        assert_eq!(locate(vec![Key("a".into()), Index(0)]), Some("3"));
+        assert_eq!(
+            Path {
+                segments: vec![Key("a/b~c".into()), Index(3)]
+            }
+            .to_string(),
+            "/a~1b~0c/3"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_from_str() {
+        use crate::de::Error;
+        use serde::Deserialize;
+        use std::borrow::Cow;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Shape {
+            Point,
+            Circle { radius: f64 },
+            Line(u32, u32),
+        }
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Doc<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            escaped: Cow<'a, str>,
+            count: u32,
+            offset: i64,
+            tags: Vec<String>,
+            missing: Option<bool>,
+            shapes: Vec<Shape>,
+        }
+
+        let src = r#"{
+  "name": "doc",
+  "escaped": "tab\there",
+  "count": 3,
+  "offset": -1,
+  "tags": ["a", "b"],
+  "missing": null,
+  "shapes": ["Point", {"Circle": {"radius": 1.5}}, {"Line": [1, 2]}]
+}"#;
+        let doc: Doc = from_str(src).unwrap();
+        assert_eq!(
+            doc,
+            Doc {
+                name: "doc",
+                escaped: Cow::Owned("tab\there".into()),
+                count: 3,
+                offset: -1,
+                tags: vec!["a".into(), "b".into()],
+                missing: None,
+                shapes: vec![
+                    Shape::Point,
+                    Shape::Circle { radius: 1.5 },
+                    Shape::Line(1, 2)
+                ],
+            }
+        );
+        // Borrowed from the source.
+        assert!(src.as_bytes().as_ptr_range().contains(&doc.name.as_ptr()));
+
+        let src = r#"{"name": "doc", "escaped": "", "count": 3, "offset": 0,
+ "tags": ["a", 7], "missing": null, "shapes": []}"#;
+        match from_str::<Doc>(src) {
+            Err(error @ Error::Data { .. }) => {
+                assert_eq!(
+                    error.to_string(),
+                    "invalid type: integer `7`, expected a string (at /tags/1)"
+                );
+                assert_eq!(&src[crate::error::Spanned::span(&error).unwrap()], "7");
+            }
+            result => panic!("{:?}", result),
+        }
+        match from_str::<Vec<u8>>("[1, 256]") {
+            Err(Error::Data { range, .. }) => assert_eq!(range, Some(4..7)),
+            result => panic!("{:?}", result),
+        }
+        match from_str::<Vec<u8>>("[1,") {
+            Err(Error::Parse(details)) => assert_eq!(details.code, error::ErrorCode::UnexpectedEof),
+            result => panic!("{:?}", result),
+        }
+        assert!(from_str::<&str>(r#""a\nb""#).is_err());
+        assert!(from_str::<(u8, u8)>("[1, 2, 3]").is_err());
+
+        // Integers beyond 64 bits aren't rounded through an `f64`.
+        let big = "[340282366920938463463374607431768211455, -170141183460469231731687303715884105728, 18446744073709551617]";
+        let (max, min, above): (u128, i128, u128) = from_str(big).unwrap();
+        assert_eq!((max, min, above), (u128::MAX, i128::MIN, 1 << 64 | 1));
+        let value: value::Value =
+            from_str("[18446744073709551617, -9223372036854775809, 1.5]").unwrap();
+        assert_eq!(
+            value.to_string(),
+            "[18446744073709551617, -9223372036854775809, 1.5]"
+        );
+        assert_eq!(from_str::<f64>("1e3").unwrap(), 1000.0);
+        assert_eq!(from_str::<f32>("2").unwrap(), 2.0);
+        assert!(from_str::<u128>("340282366920938463463374607431768211456").is_err());
+        assert!(from_str::<i128>("1.0").is_err());
+        match from_str::<value::Value>("340282366920938463463374607431768211456") {
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "invalid value: integer 340282366920938463463374607431768211456, expected a 128-bit integer"
+            ),
+            result => panic!("{:?}", result),
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_value_serde() {
+        use crate::value::{OwnedValue, Value};
+        use serde::Deserialize;
+
+        let src = r#"{"a": [1, -2, 2.5, 18446744073709551616, true, null], "b\n": "c", "d": {}}"#;
+        let value = parse_str(src).unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"a":[1,-2,2.5,18446744073709551616,true,null],"b\n":"c","d":{}}"#
+        );
+
+        // Through other deserializers, including those which don't borrow.
+        let other = r#"{"key": "value", "escaped\t": [1.5, -3, false]}"#;
+        let value: Value = serde_json::from_str(other).unwrap();
+        assert_eq!(value, parse_str(other).unwrap());
+        let owned: OwnedValue = serde_json::from_reader(other.as_bytes()).unwrap();
+        assert_eq!(owned, value);
+
+        // Embedded in a struct, through our own deserializer.
+        #[derive(Deserialize)]
+        struct Envelope<'a> {
+            kind: &'a str,
+            payload: Value<'a>,
+        }
+        let src = r#"{"kind": "event", "payload": {"id": 7, "tags": ["x"]}}"#;
+        let envelope: Envelope = from_str(src).unwrap();
+        assert_eq!(envelope.kind, "event");
+        assert_eq!(envelope.payload.to_string(), r#"{"id": 7, "tags": ["x"]}"#);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_to_string() {
+        use serde::Serialize;
+        use std::collections::BTreeMap;
+
+        #[derive(Serialize)]
+        enum Shape {
+            Point,
+            Circle { radius: f64 },
+            Line(u32, u32),
+            Named(&'static str),
+        }
+
+        #[derive(Serialize)]
+        struct Doc {
+            name: &'static str,
+            ratio: f32,
+            missing: Option<bool>,
+            empty: Vec<u8>,
+            shapes: Vec<Shape>,
+            counts: BTreeMap<u8, i64>,
+        }
+
+        let doc = Doc {
+            name: "tab\t \u{e9}",
+            ratio: 0.1,
+            missing: None,
+            empty: vec![],
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle { radius: 1.5 },
+                Shape::Line(1, 2),
+                Shape::Named("x"),
+            ],
+            counts: vec![(1, -1)].into_iter().collect(),
+        };
+        let compact = to_string(&doc).unwrap();
+        assert_eq!(
+            compact,
+            r#"{"name": "tab\t é", "ratio": 0.1, "missing": null, "empty": [], "shapes": ["Point", {"Circle": {"radius": 1.5}}, {"Line": [1, 2]}, {"Named": "x"}], "counts": {"1": -1}}"#
+        );
+        // The same text as writing the parsed value.
+        assert_eq!(parse_str(&compact).unwrap().to_string(), compact);
+
+        let pretty = to_string_pretty(&doc).unwrap();
+        let mut buf = Vec::new();
+        stringify_pretty(&mut buf, &parse_str(&compact).unwrap()).unwrap();
+        assert_eq!(pretty, String::from_utf8(buf).unwrap());
+        assert!(pretty.starts_with("{\n  \"name\": \"tab\\t é\",\n"));
+
+        let mut buf = Vec::new();
+        to_writer(&mut buf, &[1, 2]).unwrap();
+        assert_eq!(buf, b"[1, 2]");
+
+        assert!(matches!(
+            to_string(&f64::NAN),
+            Err(ser::Error::NonFiniteFloat)
+        ));
+        let map: BTreeMap<_, _> = vec![((1, 2), 3)].into_iter().collect();
+        assert!(matches!(to_string(&map), Err(ser::Error::KeyMustBeAString)));
+    }
+
+    #[test]
+    fn test_accessors() {
+        use crate::value::Value;
+        use std::collections::BTreeMap;
+
+        let mut value =
+            parse_str(r#"{"a": [1, -2.5, "s", true, null], "b": {"c": 1}, "b": {"c": 2}}"#)
+                .unwrap();
+        assert_eq!(value["a"][0].as_u64(), Some(1));
+        assert_eq!(value["a"][1].as_f64(), Some(-2.5));
+        assert_eq!(value["a"][1].as_i64(), None);
+        assert_eq!(value["a"][2].as_str(), Some("s"));
+        assert_eq!(value["a"][3].as_bool(), Some(true));
+        assert!(value["a"][4].is_null());
+        assert_eq!(value["a"].as_array().map(Vec::len), Some(5));
+        assert_eq!(value.as_object().map(Vec::len), Some(3));
+        // The last duplicate, and `Null` for anything missing.
+        assert_eq!(value["b"]["c"].as_u64(), Some(2));
+        assert!(value["missing"][7]["x"].is_null());
+        assert!(value.get("missing").is_none());
+        assert!(value["a"].get("a").is_none() && value.get(0).is_none());
+        assert_eq!(
+            value.members().map(|(key, _)| key).collect::<Vec<_>>(),
+            ["a", "b", "b"]
+        );
+        assert_eq!(value["a"].elements().count(), 5);
+        assert_eq!(value["b"].elements().count(), 0);
+
+        *value
+            .get_mut("b")
+            .unwrap()
+            .get_mut("c".to_string())
+            .unwrap() = "x".into();
+        let taken = value
+            .get_mut("a")
+            .and_then(|a| a.get_mut(2))
+            .unwrap()
+            .take();
+        assert_eq!(taken.as_str(), Some("s"));
+        assert_eq!(
+            value.to_string(),
+            r#"{"a": [1, -2.5, null, true, null], "b": {"c": 1}, "b": {"c": "x"}}"#
+        );
+
+        let map: BTreeMap<&str, Value> = vec![
+            ("n", Value::from(vec![Some(1.5), None, Some(f64::NAN)])),
+            ("s", "str".into()),
+            ("t", String::from("owned").into()),
+            ("u", ().into()),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            Value::from(map).to_string(),
+            r#"{"n": [1.5, null, null], "s": "str", "t": "owned", "u": null}"#
+        );
+        let collected: Value = (1..4u8).collect();
+        assert_eq!(collected.to_string(), "[1, 2, 3]");
+    }
+
+    // The examples of RFC 6901 section 5.
+    #[test]
+    fn test_pointer() {
+        use crate::pointer::{Pointer, PointerError};
+        use crate::value::Value;
+
+        let src = r#"{
+  "foo": ["bar", "baz"],
+  "": 0,
+  "a/b": 1,
+  "c%d": 2,
+  "e^f": 3,
+  "g|h": 4,
+  "i\\j": 5,
+  "k\"l": 6,
+  " ": 7,
+  "m~n": 8
+}"#;
+        let value = parse_str(src).unwrap();
+        let examples = [
+            ("", src),
+            ("/foo", r#"["bar", "baz"]"#),
+            ("/foo/0", r#""bar""#),
+            ("/", "0"),
+            ("/a~1b", "1"),
+            ("/c%d", "2"),
+            ("/e^f", "3"),
+            ("/g|h", "4"),
+            ("/i\\j", "5"),
+            ("/k\"l", "6"),
+            ("/ ", "7"),
+            ("/m~0n", "8"),
+        ];
+        for (pointer, expected) in examples.iter() {
+            assert_eq!(
+                value.pointer(pointer).unwrap(),
+                &parse_str(expected).unwrap(),
+                "{}",
+                pointer
+            );
+            assert_eq!(Pointer::parse(pointer).unwrap().to_string(), *pointer);
+        }
+
+        let not_found = |segment, token: &str| PointerError::NotFound {
+            segment,
+            token: token.to_string(),
+        };
+        assert_eq!(value.pointer("foo"), Err(PointerError::MissingSlash));
+        assert_eq!(value.pointer("/foo/2"), Err(not_found(1, "2")));
+        assert_eq!(value.pointer("/foo/-"), Err(not_found(1, "-")));
+        assert_eq!(value.pointer("/bar"), Err(not_found(0, "bar")));
+        assert!(matches!(
+            value.pointer("/foo/01"),
+            Err(PointerError::InvalidIndex { segment: 1, .. })
+        ));
+        assert!(matches!(
+            value.pointer("/foo/0/x"),
+            Err(PointerError::NotAContainer { segment: 2, .. })
+        ));
+        assert!(matches!(
+            value.pointer("/m~2n"),
+            Err(PointerError::InvalidEscape { segment: 0, .. })
+        ));
+        assert_eq!(
+            value.pointer("/foo/0/x").unwrap_err().to_string(),
+            "segment 3 `x`: not within an array or object"
+        );
+
+        let mut value = parse_str(r#"{"a": [1, 2], "b": {"c": 3}}"#).unwrap();
+        value.pointer_insert("/a/0", Value::from(0)).unwrap();
+        value.pointer_insert("/a/-", Value::from(3)).unwrap();
+        value.pointer_insert("/b/d", Value::from("new")).unwrap();
+        assert_eq!(
+            value.pointer_replace("/b/c", Value::Null),
+            Ok(Value::from(3))
+        );
+        assert_eq!(value.pointer_remove("/a/1"), Ok(Value::from(1)));
+        *value.pointer_mut("/a/0").unwrap() = Value::from(true);
+        assert_eq!(
+            value.to_string(),
+            r#"{"a": [true, 2, 3], "b": {"c": null, "d": "new"}}"#
+        );
+        assert_eq!(
+            value.pointer_insert("/a/4", Value::Null),
+            Err(not_found(1, "4"))
+        );
+        assert_eq!(
+            value.pointer_replace("/b/e", Value::Null),
+            Err(not_found(1, "e"))
+        );
+        assert_eq!(value.pointer_remove(""), Err(PointerError::RemoveRoot));
+        value.pointer_insert("", Value::Null).unwrap();
+        assert!(value.is_null());
+    }
+
+    #[test]
+    fn test_json_patch() {
+        use crate::patch::{apply_patch, diff, json_eq, PatchError};
+
+        // Cases from https://github.com/json-patch/json-patch-tests
+        for suite in [
+            include_str!("../tests/json-patch/spec_tests.json"),
+            include_str!("../tests/json-patch/tests.json"),
+        ]
+        .iter()
+        {
+            let cases = parse_str(suite).unwrap();
+            for case in cases.elements() {
+                if case["disabled"].as_bool() == Some(true) {
+                    continue;
+                }
+                let comment = case.get("comment").map(|c| c.to_string());
+                let comment = comment.as_deref().unwrap_or("");
+                let original = &case["doc"];
+                let mut doc = original.clone();
+                let result = apply_patch(&mut doc, &case["patch"]);
+                if case.get("error").is_some() {
+                    assert!(result.is_err(), "{}", comment);
+                    assert_eq!(&doc, original, "{}: not rolled back", comment);
+                    continue;
+                }
+                assert_eq!(result, Ok(()), "{}", comment);
+                if let Some(expected) = case.get("expected") {
+                    assert!(json_eq(&doc, expected), "{}: {}", comment, doc);
+                    let mut doc = original.clone();
+                    apply_patch(&mut doc, &diff(original, expected)).unwrap();
+                    assert!(json_eq(&doc, expected), "{}: diff gave {}", comment, doc);
+                }
+            }
+        }
+
+        let mut doc = parse_str(r#"{"a": {"b": 1}}"#).unwrap();
+        let patch = parse_str(r#"[{"op": "move", "from": "/a", "path": "/a/b/c"}]"#).unwrap();
+        assert_eq!(
+            apply_patch(&mut doc, &patch),
+            Err(PatchError::MoveIntoChild { index: 0 })
+        );
+        let patch = parse_str(
+            r#"[{"op": "test", "path": "/a/b", "value": 1.0},
+                {"op": "add", "path": "/c", "value": 2},
+                {"op": "test", "path": "/a/b", "value": 2}]"#,
+        )
+        .unwrap();
+        let error = apply_patch(&mut doc, &patch).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "operation 3: test failed, the value at `/a/b` differs"
+        );
+        assert_eq!(doc.to_string(), r#"{"a": {"b": 1}}"#);
+        let patch = parse_str(r#"[{"op": "remove", "path": "/a/x"}]"#).unwrap();
+        assert_eq!(
+            apply_patch(&mut doc, &patch).unwrap_err().to_string(),
+            "operation 1: segment 2 `x`: no such member or element"
+        );
+
+        let deep = |leaf: i32| {
+            let mut value = value::Value::from(leaf);
+            for _ in 0..100_000 {
+                value = value::Value::Array(vec![value]);
+            }
+            value
+        };
+        assert!(json_eq(&deep(1), &deep(1)));
+        assert!(!json_eq(&deep(1), &deep(2)));
+        assert!(json_eq(
+            &parse_str(r#"{"a": 1, "b": 2, "a": 3}"#).unwrap(),
+            &parse_str(r#"{"b": 2.0, "a": 3}"#).unwrap()
+        ));
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        use crate::patch::{json_eq, merge_diff, merge_patch};
+
+        // The examples of RFC 7396 Appendix A.
+        let examples = [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a": "c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a": "b", "b": "c"}"#),
+            (r#"{"a":"b"}"#, r#"{"a":null}"#, "{}"),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b": "c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a": "c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a": ["b"]}"#),
+            (
+                r#"{"a":{"b":"c"}}"#,
+                r#"{"a":{"b":"d","c":null}}"#,
+                r#"{"a": {"b": "d"}}"#,
+            ),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a": [1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c", "d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e": null, "a": 1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a": "b"}"#),
+            (
+                r#"{}"#,
+                r#"{"a":{"bb":{"ccc":null}}}"#,
+                r#"{"a": {"bb": {}}}"#,
+            ),
+        ];
+        for (target, patch, result) in examples.iter() {
+            let original = parse_str(target).unwrap();
+            let mut value = original.clone();
+            merge_patch(&mut value, &parse_str(patch).unwrap());
+            assert_eq!(value.to_string(), *result, "{} {}", target, patch);
+
+            let mut value = original.clone();
+            merge_patch(
+                &mut value,
+                &merge_diff(&original, &parse_str(result).unwrap()),
+            );
+            assert!(json_eq(&value, &parse_str(result).unwrap()), "{}", value);
+        }
+        assert_eq!(
+            merge_diff(
+                &parse_str(r#"{"a": 1, "b": {"c": 2, "d": 3}, "e": [1]}"#).unwrap(),
+                &parse_str(r#"{"b": {"d": 3, "c": 4}, "e": [1], "f": true}"#).unwrap()
+            )
+            .to_string(),
+            r#"{"a": null, "b": {"c": 4}, "f": true}"#
+        );
+
+        let deep = |leaf: i32| {
+            let mut value = value::Value::from(leaf);
+            for _ in 0..100_000 {
+                value = vec![("a", value)].into_iter().collect();
+            }
+            value
+        };
+        let mut value = deep(1);
+        merge_patch(&mut value, &deep(2));
+        assert!(json_eq(&value, &deep(2)));
+        assert!(json_eq(&merge_diff(&deep(1), &deep(2)), &deep(2)));
+        assert_eq!(merge_diff(&deep(1), &deep(1)).to_string(), "{}");
+    }
+
+    #[test]
+    fn test_compare() {
+        use crate::diff::{compare, equal, Change, DiffOptions};
+
+        let old = r#"{"name": "web", "replicas": 2, "ports": [80, 443],
+                      "env": {"A": "1", "B": "2"}, "tls": {}}"#;
+        let new = r#"{
+  "name": "web",
+  "replicas": 3,
+  "ports": [80, 8080, 443.0],
+  "env": {"B": "2", "A": "1"}
+}"#;
+        let (old_value, new_value) = (parse_str(old).unwrap(), parse_str(new).unwrap());
+        let changes = compare(&old_value, &new_value, &DiffOptions::default());
+        let described: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            described,
+            [
+                "removed /tls",
+                "changed /replicas",
+                "added /ports/1",
+                "reordered /env"
+            ]
+        );
+        let ignore_order = DiffOptions {
+            ignore_member_order: true,
+        };
+        assert_eq!(
+            compare(&old_value, &new_value, &ignore_order).len(),
+            described.len() - 1
+        );
+        assert!(!equal(&old_value, &new_value, &ignore_order));
+        assert!(equal(
+            &parse_str(r#"{"a": [1, {"b": 2, "c": 3}]}"#).unwrap(),
+            &parse_str(r#"{"a": [1.0, {"c": 3, "b": 2}]}"#).unwrap(),
+            &ignore_order
+        ));
+
+        let path = changes[1].path();
+        assert_eq!(path.locate(old).map(|range| &old[range]), Some("2"));
+        assert_eq!(path.locate(new).map(|range| &new[range]), Some("3"));
+        assert!(
+            matches!(&changes[2], Change::Added(path) if path.locate(new) == new.find("8080").map(|i| i..i + 4))
+        );
+        assert_eq!(
+            compare(
+                &parse_str("[1, 2]").unwrap(),
+                &parse_str("{}").unwrap(),
+                &ignore_order
+            ),
+            [Change::Changed(path::Path::root())]
+        );
+
+        let deep = |leaf: i32| {
+            let mut value = value::Value::from(leaf);
+            for _ in 0..100_000 {
+                value = vec![("a", value)].into_iter().collect();
+            }
+            value
+        };
+        let changes = compare(&deep(1), &deep(2), &DiffOptions::default());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path().segments.len(), 100_000);
+        assert!(equal(&deep(1), &deep(1), &DiffOptions::default()));
+        assert!(!equal(&deep(1), &deep(2), &DiffOptions::default()));
+    }
+
+    #[test]
+    fn test_diff() {
+        use crate::patch::diff;
+
+        let diff_str =
+            |old, new| diff(&parse_str(old).unwrap(), &parse_str(new).unwrap()).to_string();
+        assert_eq!(diff_str(r#"{"a": [1, 2]}"#, r#"{"a": [1, 2.0]}"#), "[]");
+        assert_eq!(
+            diff_str(r#"{"a": 1, "b": 2, "c": 3}"#, r#"{"c": 4, "b": 2, "d": 5}"#),
+            r#"[{"op": "remove", "path": "/a"}, {"op": "replace", "path": "/c", "value": 4}, {"op": "add", "path": "/d", "value": 5}]"#
+        );
+        assert_eq!(
+            diff_str("[1, 2, 3, 4]", "[1, 5, 2, 3, 4]"),
+            r#"[{"op": "add", "path": "/1", "value": 5}]"#
+        );
+        assert_eq!(
+            diff_str("[1, 2, 3, 4]", "[1, 4]"),
+            r#"[{"op": "remove", "path": "/1"}, {"op": "remove", "path": "/1"}]"#
+        );
+        assert_eq!(
+            diff_str("[1, 2, 3, 4, 5]", "[0, 1, 2, 4, 5, 6]"),
+            r#"[{"op": "add", "path": "/0", "value": 0}, {"op": "remove", "path": "/3"}, {"op": "add", "path": "/5", "value": 6}]"#
+        );
+        assert_eq!(
+            diff_str("[1, 2, 3]", "[3, 1, 2]"),
+            r#"[{"op": "add", "path": "/0", "value": 3}, {"op": "remove", "path": "/3"}]"#
+        );
+        assert_eq!(
+            diff_str("[1, [2], 3]", "[0, [2, 4], 3]"),
+            r#"[{"op": "replace", "path": "/0", "value": 0}, {"op": "add", "path": "/1/1", "value": 4}]"#
+        );
+        assert_eq!(
+            diff_str(r#"[{"a~b": 1}, 2]"#, r#"[{"a~b": [1]}, 3]"#),
+            r#"[{"op": "replace", "path": "/0/a~0b", "value": [1]}, {"op": "replace", "path": "/1", "value": 3}]"#
+        );
+        assert_eq!(
+            diff_str("{}", "[]"),
+            r#"[{"op": "replace", "path": "", "value": []}]"#
+        );
+
+        // Too long to match up by their longest common subsequence.
+        let old: value::Value = (0..100_000).collect();
+        let mut new = old.clone();
+        let elements = new.as_array_mut().unwrap();
+        elements[0] = value::Value::from(-1);
+        elements[99_999] = value::Value::from("last");
+        elements.push(value::Value::Null);
+        assert_eq!(
+            diff(&old, &new).to_string(),
+            r#"[{"op": "replace", "path": "/0", "value": -1}, {"op": "replace", "path": "/99999", "value": "last"}, {"op": "add", "path": "/100000", "value": null}]"#
+        );
+        let mut patched = old.clone();
+        crate::patch::apply_patch(&mut patched, &diff(&old, &new)).unwrap();
+        assert_eq!(patched, new);
+    }
+
+    #[test]
+    fn test_line_col() {
+        use crate::error::Spanned as _;
+        use crate::position::{LineCol, LineIndex};
+
+        let src = "{\r\n  \"😀\": [1,\n  \"é\" 2]\n}";
+        let index = LineIndex::new(src);
+        let error = parse_str(src).unwrap_err();
+        let offset = src.find('2').unwrap();
+        assert_eq!(error.span(), Some(offset..offset + 1));
+        assert_eq!(error.line_col(&index), Some(LineCol { line: 2, column: 6 }));
+        assert_eq!(index.line_col_utf8(offset).column, 7);
+        assert_eq!(index.line_col(0), LineCol { line: 0, column: 0 });
+        assert_eq!(index.line_col(src.len()), LineCol { line: 3, column: 1 });
+
+        let colon = src.find(':').unwrap();
+        assert_eq!(index.line_col(colon), LineCol { line: 1, column: 5 });
+        assert_eq!(index.line_col_utf16(colon), LineCol { line: 1, column: 6 });
+        assert_eq!(index.line_col_utf8(colon), LineCol { line: 1, column: 8 });
+        assert_eq!(index.line_col(colon).to_string(), "line 2, column 6");
+    }
+
+    #[test]
+    fn test_error_messages() {
+        use crate::error::{JsonPopError, Located};
+        use crate::position::LineIndex;
+        use std::error::Error as _;
+
+        let message = |src: &str| {
+            let index = LineIndex::new(src);
+            let error = JsonPopError::from(parse_str(src).unwrap_err());
+            Located {
+                error: &error,
+                index: &index,
+            }
+            .to_string()
+        };
+        assert_eq!(
+            message("[1}"),
+            "expected a comma or closing bracket, found a closing brace at line 1, column 3"
+        );
+        assert_eq!(
+            message("{\n  \"a\":\n    [true, \"b]\n}"),
+            "unterminated string literal at line 3, column 12"
+        );
+        assert_eq!(
+            message("[1, 2"),
+            "unexpected end of input, expected a comma or closing bracket at line 1, column 6"
+        );
+        assert_eq!(
+            message("[\"\\udc00\"]"),
+            "invalid unicode escape, unpaired surrogate at line 1, column 3"
+        );
+
+        // Internal terminals such as `missing_quote` aren't listed.
+        assert_eq!(
+            message("[1,]"),
+            "expected a value, found a closing bracket at line 1, column 4"
+        );
+        assert_eq!(
+            message("{\"a\": 1, 2}"),
+            "expected a string, found number 2 at line 1, column 10"
+        );
+
+        // The message isn't repeated by `source`.
+        let error = JsonPopError::from(parse_str("\"\\ud800\"").unwrap_err());
+        assert_eq!(
+            error.to_string(),
+            "invalid unicode escape, unpaired surrogate"
+        );
+        assert!(error.source().is_none());
+    }
+
+    #[test]
+    fn test_error_codes() {
+        use crate::error::{ErrorCode, JsonPopError, ParseErrorDetails, TopLevelError};
+        use crate::position::LineCol;
+
+        let src = "[1,\n 2 \"b\"]";
+        let error =
+            TopLevelError::from_source(JsonPopError::from(parse_str(src).unwrap_err()), src);
+        assert_eq!(error.code(), Some(ErrorCode::UnexpectedToken));
+        match &error {
+            TopLevelError::ParseError(details) => assert_eq!(
+                *details,
+                ParseErrorDetails {
+                    code: ErrorCode::UnexpectedToken,
+                    message: "expected a comma, closing bracket or closing brace, found string literal \"b\""
+                        .to_string(),
+                    range: 7..10,
+                    line_col: Some(LineCol { line: 1, column: 3 }),
+                    expected: vec![
+                        "comma".to_string(),
+                        "closing bracket".to_string(),
+                        "closing brace".to_string()
+                    ],
+                }
+            ),
+            _ => panic!("expected a parse error: {:?}", error),
+        }
+        assert_eq!(
+            error.to_string(),
+            "JP0006: expected a comma, closing bracket or closing brace, found string literal \"b\" at line 2, column 4"
+        );
+
+        for (src, code) in &[
+            ("\"abc", "JP0001"),
+            ("[1, @]", "JP0002"),
+            ("\"\\udfff\"", "JP0004"),
+            ("[1, 2", "JP0005"),
+        ] {
+            let error = TopLevelError::from(JsonPopError::from(parse_str(src).unwrap_err()));
+            assert_eq!(error.code().map(ErrorCode::as_str), Some(*code), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        use crate::options::ParseOptions;
+        use crate::value::Value;
+        use lalrpop_util::ParseError::User;
+
+        let depth = |n| "[".repeat(n) + &"]".repeat(n);
+        let options = ParseOptions {
+            max_depth: Some(3),
+            ..ParseOptions::default()
+        };
+        assert!(parse_str_with(&depth(3), &options).is_ok());
+        match parse_str_with(&format!("{{\"a\": {}}}", depth(3)), &options) {
+            Err(User {
+                error: CompilationError::DepthLimitExceeded { range },
+            }) => assert_eq!(range, 8..9),
+            result => panic!("{:?}", result),
+        }
+        assert!(parse_str_with(&depth(129), &Default::default()).is_err());
+        assert!(parse_str_with(&depth(128), &Default::default()).is_ok());
+        assert!(parse_str(&depth(129)).is_ok());
+
+        let unlimited = ParseOptions {
+            max_depth: None,
+            ..ParseOptions::default()
+        };
+        let src = depth(50_000);
+        let deep = parse_str_with(&src, &unlimited).unwrap();
+        assert_eq!(deep.to_string(), src);
+        drop(deep);
+
+        let mut deep = Value::Null;
+        for _ in 0..1_000_000 {
+            deep = Value::Object(vec![("a".into(), Value::Array(vec![deep]))]);
+        }
+        assert!(deep.to_string().starts_with(r#"{"a": [{"a": [{"#));
+        // Compared with `assert!`, as `Debug` of a failure would recurse.
+        let copy = deep.clone();
+        assert!(copy == deep);
+        assert!(copy.as_borrowed() == deep);
+        let owned = copy.into_owned();
+        assert!(owned == deep);
+        let mut other = Value::Null;
+        for _ in 0..1_000_000 {
+            other = Value::Object(vec![("a".into(), Value::Array(vec![other]))]);
+        }
+        assert!(other == deep);
+        *other.pointer_mut("/a/0/a/0").unwrap() = Value::Bool(true);
+        assert!(other != deep);
+    }
+
+    #[test]
+    fn test_resource_limits() {
+        use crate::error::ErrorCode;
+        use crate::extra::source::{Parsable as _, Source};
+        use crate::options::ParseOptions;
+        use lalrpop_util::ParseError::User;
+
+        let src = r#"{"abc": [1, 2, 3], "d": "efgh"}"#;
+        let limit = |options: ParseOptions| {
+            let source = Source::from(&src).with_options(options);
+            let limit = match source.parse().parse_result.map_err(|e| e.error) {
+                Ok(_) => None,
+                Err(User { error }) => Some((error.code(), error.range())),
+                Err(error) => panic!("{:?}", error),
+            };
+            limit
+        };
+        let defaults = ParseOptions::default;
+
+        assert_eq!(limit(defaults()), None);
+        assert_eq!(
+            limit(ParseOptions {
+                max_input_size: Some(10),
+                ..defaults()
+            }),
+            Some((ErrorCode::InputTooLarge, 10..src.len()))
+        );
+        assert_eq!(
+            limit(ParseOptions {
+                max_string_length: Some(3),
+                ..defaults()
+            }),
+            Some((ErrorCode::StringTooLong, 24..30))
+        );
+        assert_eq!(
+            limit(ParseOptions {
+                max_array_length: Some(2),
+                ..defaults()
+            }),
+            Some((ErrorCode::ArrayTooLong, 15..16))
+        );
+        assert_eq!(
+            limit(ParseOptions {
+                max_object_members: Some(1),
+                ..defaults()
+            }),
+            Some((ErrorCode::ObjectTooLarge, 19..22))
+        );
+        assert_eq!(
+            limit(ParseOptions {
+                max_values: Some(5),
+                ..defaults()
+            }),
+            Some((ErrorCode::TooManyValues, 24..30))
+        );
+        assert_eq!(
+            limit(ParseOptions {
+                max_input_size: Some(src.len()),
+                max_string_length: Some(4),
+                max_array_length: Some(3),
+                max_object_members: Some(2),
+                max_values: Some(6),
+                ..defaults()
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        use crate::error::CompilationError;
+        use crate::options::{DuplicateKeys, ParseOptions};
+        use lalrpop_util::ParseError::User;
+
+        let src = r#"{"a": 1, "b": {"c": 2, "c": 3}, "a": 4}"#;
+        let parse = |duplicate_keys| {
+            let options = ParseOptions {
+                duplicate_keys,
+                ..ParseOptions::default()
+            };
+            parse_str_with(src, &options).map(|value| value.to_string())
+        };
+
+        assert_eq!(parse(DuplicateKeys::KeepAll).unwrap(), src);
+        assert_eq!(
+            parse(DuplicateKeys::KeepFirst).unwrap(),
+            r#"{"a": 1, "b": {"c": 2}}"#
+        );
+        assert_eq!(
+            parse(DuplicateKeys::KeepLast).unwrap(),
+            r#"{"a": 4, "b": {"c": 3}}"#
+        );
+        match parse(DuplicateKeys::Reject) {
+            Err(User {
+                error: CompilationError::DuplicateKey { first, second },
+            }) => assert_eq!((first, second), (15..18, 23..26)),
+            result => panic!("{:?}", result),
+        }
+        // Keys are compared after escapes are decoded.
+        let options = ParseOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..ParseOptions::default()
+        };
+        assert!(parse_str_with(r#"{"a": 1, "\u0061": 2}"#, &options).is_err());
+    }
+
+    // A small fuzzer, parsing pseudo-random input with tight limits
+    // to check that no input panics.
+    #[test]
+    fn test_fuzz_limits() {
+        use crate::options::{DuplicateKeys, ParseOptions};
+
+        let pieces = [
+            "[",
+            "]",
+            "{",
+            "}",
+            ",",
+            ":",
+            "\"a\"",
+            "\"\\u00e9\"",
+            "\"\\ud800\"",
+            "\"",
+            "1",
+            "-0.5e3",
+            "true",
+            "null",
+            " ",
+            "\n",
+            "é",
+            "\\",
+            "01",
+            "x",
+        ];
+        let options = ParseOptions {
+            max_depth: Some(4),
+            max_input_size: Some(64),
+            max_string_length: Some(4),
+            max_array_length: Some(3),
+            max_object_members: Some(3),
+            max_values: Some(8),
+            duplicate_keys: DuplicateKeys::Reject,
+        };
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = || {
+            // xorshift64
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        for _ in 0..20_000 {
+            let len = next() % 24;
+            let src: String = (0..len)
+                .map(|_| pieces[(next() % pieces.len() as u64) as usize])
+                .collect();
+            for options in &[&options, &ParseOptions::default()] {
+                let recovered = parse_str_recovering(&src, options);
+                match parse_str_with(&src, options) {
+                    Ok(value) => {
+                        assert!(recovered.errors.is_empty(), "{}", src);
+                        assert_eq!(parse_str(&value.to_string()).ok(), Some(value));
+                    }
+                    Err(_) => assert!(!recovered.errors.is_empty(), "{}", src),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_recovering() {
+        use crate::error::{parse_error_message, parse_error_range};
+        use crate::options::{DuplicateKeys, ParseOptions};
+
+        let options = ParseOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..ParseOptions::default()
+        };
+        let recovered = |src| {
+            let recovered = parse_str_recovering(src, &options);
+            let errors: Vec<_> = recovered
+                .errors
+                .iter()
+                .map(|e| (parse_error_range(&e.error), parse_error_message(&e.error)))
+                .collect();
+            (recovered.value.map(|value| value.to_string()), errors)
+        };
+
+        let (value, errors) = recovered(r#"{"a": 1 2, "b": [1,, 3], "c": tru, "h": [1, 2}"#);
+        assert_eq!(
+            value.as_deref(),
+            Some(r#"{"b": [1, null, 3], "c": null, "h": [1, 2]}"#)
+        );
+        let ranges: Vec<_> = errors.iter().map(|(range, _)| range.clone()).collect();
+        assert_eq!(ranges, vec![8..9, 19..20, 30..31, 45..46]);
+        assert_eq!(errors[2].1, "invalid token");
+
+        // Placeholders can be told apart from nulls in the source.
+        let src = r#"[null, @, 2, "x"#;
+        let nulls = parse_str_recovering(src, &options);
+        assert_eq!(nulls.value.unwrap().to_string(), "[null, null, 2, null]");
+        let placeholders: Vec<_> = nulls
+            .placeholders
+            .iter()
+            .map(|range| &src[range.clone()])
+            .collect();
+        assert_eq!(placeholders, ["@", "\"x"]);
+        assert_eq!(
+            parse_str_recovering("[1,,,]", &options).placeholders,
+            [3..3, 4..4, 5..5]
+        );
+
+        // The first error is the one `parse_str` gives.
+        for src in &[r#"[1, 2"#, r#"{"a" 1, "b": 2}"#, r#"[1 2, 3]"#, "[@, 1] 2"] {
+            let (value, errors) = recovered(src);
+            assert!(value.is_some(), "{}", src);
+            let error = parse_str_with(src, &options).unwrap_err();
+            assert_eq!(errors[0].1, parse_error_message(&error));
+        }
+        assert_eq!(recovered("[1, 2").0.as_deref(), Some("[1, 2]"));
+        assert_eq!(recovered("[@, 1] 2").1.len(), 2);
+
+        // Rejected duplicate keys are reported, and kept.
+        let (value, errors) = recovered(r#"{"a": 1, "a": 2}"#);
+        assert_eq!(value.as_deref(), Some(r#"{"a": 1, "a": 2}"#));
+        assert_eq!(errors, vec![(9..12, "duplicate key".to_string())]);
+
+        assert_eq!(recovered("]").0.as_deref(), Some("null"));
+
+        // Limits aren't recovered from.
+        let limited = ParseOptions {
+            max_depth: Some(1),
+            ..ParseOptions::default()
+        };
+        let recovered = parse_str_recovering("[[1], @]", &limited);
+        assert!(recovered.value.is_none());
+        assert_eq!(recovered.errors.len(), 1);
+
+        // A limit exceeded after a syntax error, while the parser skips
+        // tokens to recover, doesn't hide the syntax error.
+        let src = format!("[1 2 {}", "[".repeat(200));
+        let error = parse_str(&src).unwrap_err();
+        assert_eq!(parse_error_range(&error), 3..4);
+        assert!(matches!(
+            error,
+            lalrpop_util::ParseError::UnrecognizedToken { .. }
+        ));
+        let recovered = parse_str_recovering(&src, &ParseOptions::default());
+        assert!(recovered.value.is_none());
+        let ranges: Vec<_> = recovered
+            .errors
+            .iter()
+            .map(|e| parse_error_range(&e.error))
+            .collect();
+        assert_eq!(ranges, vec![3..4, 132..133]);
+        assert!(matches!(
+            recovered.errors[1].error,
+            lalrpop_util::ParseError::User {
+                error: CompilationError::DepthLimitExceeded { .. }
+            }
+        ));
+
+        let ok = parse_str_recovering("[1, {\"a\": true}]", &options);
+        assert_eq!(
+            ok.into_result().unwrap(),
+            parse_str("[1, {\"a\": true}]").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_fix() {
+        use crate::fix::{fix, suggest};
+
+        let error = |src| parse_str_bracketed(src, &Default::default()).unwrap_err();
+        let suggestion = |src| suggest(src, &error(src)).unwrap();
+        let s = suggestion("[1, 2,]");
+        assert_eq!((s.range, s.replacement.as_str()), (5..6, ""));
+        assert_eq!(s.message, "remove the trailing comma");
+        let s = suggestion("{\"a\": 1 \"b\": 2}");
+        assert_eq!((s.range, s.replacement.as_str()), (7..7, ","));
+        let s = suggestion("{\"a\" 1}");
+        assert_eq!((s.range, s.replacement.as_str()), (4..4, ":"));
+        let s = suggestion("['it\\'s \"x\"']");
+        assert_eq!(
+            (s.range, s.replacement.as_str()),
+            (1..12, r#""it's \"x\"""#)
+        );
+        let s = suggestion("{ key_1: true}");
+        assert_eq!((s.range, s.replacement.as_str()), (2..7, "\"key_1\""));
+        let s = suggestion("{\"a\": 1 b: 2}");
+        assert_eq!((s.range, s.replacement.as_str()), (7..9, ", \"b\""));
+        assert_eq!(s.message, "insert a comma and quote the key");
+        let s = suggestion("[{\"a\": [1]");
+        assert_eq!((s.range, s.replacement.as_str()), (10..10, "}]"));
+        let s = suggestion("[1,\n");
+        assert_eq!((s.range, s.replacement.as_str()), (2..3, "]"));
+        let s = suggestion("{\"a\": [1, 2}");
+        assert_eq!((s.range, s.replacement.as_str()), (11..12, "]"));
+
+        // Mistakes which aren't recognized.
+        for src in &[
+            "[1 : 2]",
+            "{\"a\": }",
+            "tru",
+            "[1, 2]]",
+            "{1: 2}",
+            "[1, 2] 3",
+            "{} {}",
+        ] {
+            assert_eq!(suggest(src, &error(src)), None, "{}", src);
+        }
+
+        let (fixed, applied) = fix("{a: 1, 'b': 'c', d: [1 2,], e: true,");
+        assert_eq!(fixed, r#"{"a": 1, "b": "c", "d": [1, 2], "e": true}"#);
+        assert_eq!(applied.len(), 8);
+        assert_eq!(fix("{a:1 b:2}").0, r#"{"a":1, "b":2}"#);
+        let (fixed, applied) = fix("{a: 1, b: [1 2 3], c: {d: null e: true}}");
+        assert_eq!(
+            fixed,
+            r#"{"a": 1, "b": [1, 2, 3], "c": {"d": null, "e": true}}"#
+        );
+        assert_eq!(applied.len(), 7);
+        assert_eq!(fix("[1, 2]"), ("[1, 2]".to_string(), vec![]));
+        assert_eq!(fix("[1 : 2]").1, vec![]);
+        assert_eq!(fix("[1, 2] 3"), ("[1, 2] 3".to_string(), vec![]));
+    }
+
+    #[test]
+    fn test_brackets() {
+        use crate::brackets::{Bracket, Open, Unmatched};
+        use crate::options::ParseOptions;
+
+        let error = |src| parse_str_bracketed(src, &ParseOptions::default()).unwrap_err();
+        let found = error("[{\"a\": [1]");
+        assert_eq!(
+            found.open,
+            vec![
+                Open {
+                    bracket: Bracket::Square,
+                    range: 0..1
+                },
+                Open {
+                    bracket: Bracket::Curly,
+                    range: 1..2
+                },
+            ]
+        );
+        let found = found.unmatched().unwrap();
+        assert_eq!(found.open().range, 1..2);
+        assert_eq!(found.to_string(), "this `{` was never closed");
+
+        let found = error("{\"a\": [1, 2}").unmatched().unwrap();
+        assert_eq!(
+            found,
+            Unmatched::Mismatched {
+                open: Open {
+                    bracket: Bracket::Square,
+                    range: 6..7
+                },
+                close: 11..12,
+            }
+        );
+        assert_eq!(found.to_string(), "`}` does not match `[` opened here");
+
+        for src in &["[1, 2,]", "[1 2]", "1 ]", "\"a"] {
+            assert_eq!(error(src).unmatched(), None, "{}", src);
+        }
+
+        // As the parser matched them while recovering, which skipped the `{`
+        // of the first, and gave up on the `{` of the second.
+        let starts = |src| {
+            let recovered = parse_str_recovering(src, &ParseOptions::default());
+            let starts: Vec<Vec<_>> = recovered
+                .errors
+                .iter()
+                .map(|e| e.open.iter().map(|open| open.range.start).collect())
+                .collect();
+            starts
+        };
+        assert_eq!(starts("[1 {] [2"), vec![vec![0], vec![]]);
+        assert_eq!(starts("[1, {] 2"), vec![vec![0, 4], vec![]]);
+    }
+
+    #[cfg(feature = "pretty_errors")]
+    #[test]
+    fn test_codespan_files() {
+        use crate::extra::codespan::{add_parse_errors, Summary};
+        use codespan_reporting::files::SimpleFiles;
+
+        let sources = [
+            ("a.json", "[1, 2}".to_string()),
+            ("b.json", "{\"a\": 1}".to_string()),
+            ("c.json", "{\"a\" 1, \"b\": [1,]}".to_string()),
+        ];
+        let mut files = SimpleFiles::new();
+        let mut diagnostics = Vec::new();
+        let mut summary = Summary::default();
+        for (name, source) in &sources {
+            let errors = parse_str_recovering(source, &Default::default()).errors;
+            let found = add_parse_errors(&mut files, *name, source.as_str(), &errors);
+            summary.add_file(&found);
+            diagnostics.extend(found);
+        }
+        let file_names: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| files.get(diagnostic.labels[0].file_id).unwrap().name())
+            .collect();
+        assert_eq!(file_names, vec![&"a.json", &"c.json", &"c.json"]);
+        assert_eq!(summary.to_string(), "3 errors in 2 of 3 files");
+
+        let mut summary = Summary::default();
+        summary.add_file(&[]);
+        assert_eq!(summary.to_string(), "no errors in 1 file");
+    }
+
+    #[cfg(feature = "pretty_errors")]
+    #[test]
+    fn test_codespan_changes() {
+        use crate::diff::{compare, DiffOptions};
+        use crate::extra::codespan::from_changes;
+
+        let (old, new) = (r#"{"a": 1, "a": 2}"#, r#"{"a": 1, "a": 3}"#);
+        let changes = compare(
+            &parse_str(old).unwrap(),
+            &parse_str(new).unwrap(),
+            &DiffOptions::default(),
+        );
+        let (files, diagnostics) = from_changes(("old.json", old), ("new.json", new), &changes);
+        assert_eq!(diagnostics.len(), 1);
+        let labelled: Vec<_> = diagnostics[0]
+            .labels
+            .iter()
+            .map(|label| &files.get(label.file_id).unwrap().source()[label.range.clone()])
+            .collect();
+        assert_eq!(labelled, ["2", "3"]);
+    }
 }
@@ -0,0 +1,300 @@
+use crate::brackets::{self, Bracket, Seen};
+use crate::error::CompilationError;
+use crate::lex::Token;
+use crate::value::Value;
+use logos::Logos as _;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Options controlling how input is parsed.
+///
+/// Each limit is `None` for no limit, and is reported as a `CompilationError`
+/// with the range at which the limit was exceeded.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// The maximum nesting depth of arrays and objects.
+    ///
+    /// Defaults to 128, but `parse_str` has no limit, as it never had.
+    /// Values of any depth can be parsed, displayed,
+    /// cloned, compared and dropped, but without a limit a deep enough value
+    /// overflows the stack when formatted with `Debug`,
+    /// or serialized or deserialized with serde.
+    pub max_depth: Option<usize>,
+    /// The maximum size of the input in bytes.
+    pub max_input_size: Option<usize>,
+    /// The maximum length of a string or key in bytes,
+    /// as written in the source before escapes are decoded.
+    pub max_string_length: Option<usize>,
+    /// The maximum number of elements in an array.
+    pub max_array_length: Option<usize>,
+    /// The maximum number of members in an object.
+    pub max_object_members: Option<usize>,
+    /// The maximum number of values in the document, including nested values.
+    pub max_values: Option<usize>,
+    /// What to do with objects which contain the same key more than once.
+    ///
+    /// Defaults to `DuplicateKeys::KeepAll`.
+    pub duplicate_keys: DuplicateKeys,
+}
+
+/// What to do with objects which contain the same key more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Keep every member.
+    KeepAll,
+    /// Fail with `CompilationError::DuplicateKey`.
+    Reject,
+    /// Keep the first member with each key.
+    KeepFirst,
+    /// Keep the value of the last member with each key,
+    /// at the position of the first.
+    KeepLast,
+}
+
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_depth: Some(128),
+            max_input_size: None,
+            max_string_length: None,
+            max_array_length: None,
+            max_object_members: None,
+            max_values: None,
+            duplicate_keys: DuplicateKeys::KeepAll,
+        }
+    }
+}
+
+pub type Triple<'a> = Result<(usize, Token<'a>, usize), CompilationError>;
+
+/// An object member with the range of its key, as parsed.
+pub type Member<'a> = (Range<usize>, Cow<'a, str>, Value<'a>);
+
+/// The error for the first key used more than once, when `policy` rejects them.
+pub fn duplicate_key(policy: DuplicateKeys, members: &[Member<'_>]) -> Option<CompilationError> {
+    if policy != DuplicateKeys::Reject {
+        return None;
+    }
+    let mut seen = HashMap::with_capacity(members.len());
+    members.iter().find_map(|(range, key, _)| {
+        seen.insert(key.as_ref(), range)
+            .map(|first| CompilationError::DuplicateKey {
+                first: first.clone(),
+                second: range.clone(),
+            })
+    })
+}
+
+/// Applies `policy` to the members of an object.
+pub fn resolve_duplicate_keys(
+    policy: DuplicateKeys,
+    members: Vec<Member<'_>>,
+) -> Result<Vec<(Cow<'_, str>, Value<'_>)>, CompilationError> {
+    if let Some(error) = duplicate_key(policy, &members) {
+        return Err(error);
+    }
+    if let DuplicateKeys::KeepAll | DuplicateKeys::Reject = policy {
+        return Ok(members
+            .into_iter()
+            .map(|(_, key, value)| (key, value))
+            .collect());
+    }
+
+    // The index of the first member with the same key as each member.
+    let firsts: Vec<usize> = {
+        let mut seen = HashMap::with_capacity(members.len());
+        members
+            .iter()
+            .enumerate()
+            .map(|(i, (_, key, _))| *seen.entry(key.as_ref()).or_insert(i))
+            .collect()
+    };
+
+    // The index in `kept` of the first members with each key.
+    let mut kept_at = vec![0; members.len()];
+    let mut kept = Vec::with_capacity(members.len());
+    for (i, (_, key, value)) in members.into_iter().enumerate() {
+        let first = firsts[i];
+        if first == i {
+            kept_at[i] = kept.len();
+            kept.push((key, value));
+        } else if policy == DuplicateKeys::KeepLast {
+            kept[kept_at[first]].1 = value;
+        }
+    }
+    Ok(kept)
+}
+
+/// The tokens of `source` as given to the parser,
+/// with the limits of `options` enforced as they are lexed.
+pub fn tokens<'a, 'o>(
+    source: &'a str,
+    options: &'o ParseOptions,
+) -> impl Iterator<Item = Triple<'a>> + 'o
+where
+    'a: 'o,
+{
+    Checked::new(source, options)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Array,
+    Object,
+}
+
+/// An array or object which has been opened but not yet closed.
+struct Open {
+    kind: Kind,
+    /// The number of elements or members so far.
+    len: usize,
+    /// Whether the next token starts an element or member,
+    /// following the opening bracket or a comma.
+    expecting_element: bool,
+}
+
+/// Checks the tokens against the limits before the parser sees them,
+/// so the parser stops before allocating anything past a limit.
+///
+/// Counting is done on tokens alone, so invalid input may be miscounted,
+/// but the parser rejects such input regardless.
+pub(crate) struct Checked<'a, 'o> {
+    /// Given before any tokens when the input exceeds `max_input_size`.
+    too_large: Option<CompilationError>,
+    tokens: logos::SpannedIter<'a, Token<'a>>,
+    options: &'o ParseOptions,
+    open: Vec<Open>,
+    values: usize,
+    /// Each bracket given to the parser, in order,
+    /// for `brackets::Bracketed::attach`.
+    pub(crate) brackets: Vec<Seen>,
+}
+
+fn exceeds(limit: Option<usize>, n: usize) -> bool {
+    matches!(limit, Some(max) if n > max)
+}
+
+impl<'a, 'o> Checked<'a, 'o> {
+    pub(crate) fn new(source: &'a str, options: &'o ParseOptions) -> Checked<'a, 'o> {
+        let too_large = match options.max_input_size {
+            Some(max) if source.len() > max => Some(CompilationError::InputTooLarge {
+                range: max..source.len(),
+            }),
+            _ => None,
+        };
+        Checked {
+            too_large,
+            tokens: Token::lexer(source).spanned(),
+            options,
+            open: Vec::new(),
+            values: 0,
+            brackets: Vec::new(),
+        }
+    }
+
+    fn check(
+        &mut self,
+        start: usize,
+        token: &Token<'a>,
+        end: usize,
+    ) -> Result<(), CompilationError> {
+        let range = start..end;
+        let closing = matches!(token, Token::RBrace | Token::RBrack);
+        let mut is_key = false;
+        if let Some(open) = self.open.last_mut() {
+            if open.expecting_element && !closing {
+                open.expecting_element = false;
+                open.len += 1;
+                match open.kind {
+                    Kind::Array if exceeds(self.options.max_array_length, open.len) => {
+                        return Err(CompilationError::ArrayTooLong { range })
+                    }
+                    Kind::Object if exceeds(self.options.max_object_members, open.len) => {
+                        return Err(CompilationError::ObjectTooLarge { range })
+                    }
+                    Kind::Array => (),
+                    Kind::Object => is_key = true,
+                }
+            }
+        }
+
+        match token {
+            Token::True
+            | Token::False
+            | Token::Null
+            | Token::Number(_)
+            | Token::String(_)
+            | Token::LBrace
+            | Token::LBrack
+                if !is_key =>
+            {
+                self.values += 1;
+                if exceeds(self.options.max_values, self.values) {
+                    return Err(CompilationError::TooManyValues { range });
+                }
+            }
+            _ => (),
+        }
+
+        match token {
+            Token::LBrace | Token::LBrack => {
+                self.brackets.push(Seen::Open(brackets::Open {
+                    bracket: if let Token::LBrace = token {
+                        Bracket::Curly
+                    } else {
+                        Bracket::Square
+                    },
+                    range: range.clone(),
+                }));
+                self.open.push(Open {
+                    kind: if let Token::LBrace = token {
+                        Kind::Object
+                    } else {
+                        Kind::Array
+                    },
+                    len: 0,
+                    expecting_element: true,
+                });
+                if exceeds(self.options.max_depth, self.open.len()) {
+                    return Err(CompilationError::DepthLimitExceeded { range });
+                }
+            }
+            Token::RBrace | Token::RBrack => {
+                let bracket = if let Token::RBrace = token {
+                    Bracket::Curly
+                } else {
+                    Bracket::Square
+                };
+                self.brackets.push(Seen::Close(bracket, range));
+                self.open.pop();
+            }
+            Token::Comma => {
+                if let Some(open) = self.open.last_mut() {
+                    open.expecting_element = true;
+                }
+            }
+            // The length without the quotes.
+            Token::String(s) if exceeds(self.options.max_string_length, s.len() - 2) => {
+                return Err(CompilationError::StringTooLong { range });
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl<'a, 'o> Iterator for Checked<'a, 'o> {
+    type Item = Triple<'a>;
+
+    fn next(&mut self) -> Option<Triple<'a>> {
+        if let Some(error) = self.too_large.take() {
+            return Some(Err(error));
+        }
+        let (start, token, end) = match Token::to_lalr_triple(self.tokens.next()?) {
+            Ok(triple) => triple,
+            err => return Some(err),
+        };
+        Some(self.check(start, &token, end).map(|()| (start, token, end)))
+    }
+}
@@ -0,0 +1,582 @@
+//! JSON Patch (RFC 6902), applying a list of operations such as
+//! `[{"op": "add", "path": "/a/b", "value": 1}]` to a `Value`,
+//! and finding the patch between two values.
+//!
+//! Also JSON Merge Patch (RFC 7396), where the patch is a document
+//! such as `{"a": {"b": 1, "c": null}}` giving the members to set or remove.
+use crate::pointer::{Pointer, PointerError};
+use crate::value::Value;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// One operation of a patch.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<'a> {
+    /// Adds a member to an object, inserts an element into an array,
+    /// or replaces the whole document, see `Pointer::insert`.
+    Add {
+        path: Pointer,
+        value: Value<'a>,
+    },
+    Remove {
+        path: Pointer,
+    },
+    /// Replaces an existing value.
+    Replace {
+        path: Pointer,
+        value: Value<'a>,
+    },
+    /// Removes the value at `from` and adds it at `path`.
+    Move {
+        from: Pointer,
+        path: Pointer,
+    },
+    /// Adds a copy of the value at `from` at `path`.
+    Copy {
+        from: Pointer,
+        path: Pointer,
+    },
+    /// Fails the patch unless the value at `path` equals `value`.
+    Test {
+        path: Pointer,
+        value: Value<'a>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch is not an array of operations.
+    NotAnArray,
+    /// The operation is not an object with the members its `op` needs.
+    InvalidOperation { index: usize, reason: String },
+    /// A pointer of the operation is invalid, or doesn't refer to a value.
+    Pointer { index: usize, error: PointerError },
+    /// The value at `path` of a `test` operation is not the one expected.
+    TestFailed { index: usize, path: Pointer },
+    /// The `path` of a `move` operation is within its `from`.
+    MoveIntoChild { index: usize },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Operations are displayed counting from 1.
+        match self {
+            PatchError::NotAnArray => write!(f, "a JSON patch must be an array of operations"),
+            PatchError::InvalidOperation { index, reason } => {
+                write!(f, "operation {}: {}", index + 1, reason)
+            }
+            PatchError::Pointer { index, error } => write!(f, "operation {}: {}", index + 1, error),
+            PatchError::TestFailed { index, path } => write!(
+                f,
+                "operation {}: test failed, the value at `{}` differs",
+                index + 1,
+                path
+            ),
+            PatchError::MoveIntoChild { index } => write!(
+                f,
+                "operation {}: a value can't be moved into one of its own members",
+                index + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Applies the JSON patch `patch` to `doc`.
+///
+/// The patch is applied atomically, if any operation fails
+/// `doc` is left as it was and the error is returned.
+pub fn apply_patch<'a>(doc: &mut Value<'a>, patch: &Value<'a>) -> Result<(), PatchError> {
+    apply_operations(doc, &operations(patch)?)
+}
+
+/// Like `apply_patch`, but with operations already read by `operations`.
+pub fn apply_operations<'a>(
+    doc: &mut Value<'a>,
+    operations: &[Operation<'a>],
+) -> Result<(), PatchError> {
+    // Operations are applied to a copy, which replaces `doc` once they all succeed.
+    let mut patched = doc.clone();
+    for (index, operation) in operations.iter().enumerate() {
+        apply_operation(&mut patched, index, operation)?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+fn apply_operation<'a>(
+    doc: &mut Value<'a>,
+    index: usize,
+    operation: &Operation<'a>,
+) -> Result<(), PatchError> {
+    let pointer_error = |error| PatchError::Pointer { index, error };
+    match operation {
+        Operation::Add { path, value } => path.insert(doc, value.clone()).map_err(pointer_error),
+        Operation::Remove { path } => path.remove(doc).map(drop).map_err(pointer_error),
+        Operation::Replace { path, value } => path
+            .replace(doc, value.clone())
+            .map(drop)
+            .map_err(pointer_error),
+        Operation::Move { from, path } => {
+            if from == path {
+                return from.get(doc).map(drop).map_err(pointer_error);
+            }
+            if path.tokens().starts_with(from.tokens()) {
+                return Err(PatchError::MoveIntoChild { index });
+            }
+            let value = from.remove(doc).map_err(pointer_error)?;
+            path.insert(doc, value).map_err(pointer_error)
+        }
+        Operation::Copy { from, path } => {
+            let value = from.get(doc).map_err(pointer_error)?.clone();
+            path.insert(doc, value).map_err(pointer_error)
+        }
+        Operation::Test { path, value } => {
+            if json_eq(path.get(doc).map_err(pointer_error)?, value) {
+                Ok(())
+            } else {
+                Err(PatchError::TestFailed {
+                    index,
+                    path: path.clone(),
+                })
+            }
+        }
+    }
+}
+
+/// Reads the operations of a JSON patch.
+///
+/// Members of an operation other than `op`, `path`, `from` and `value`
+/// are ignored, and an operation with more than one member named
+/// `op`, or any other member it uses, is rejected.
+pub fn operations<'a>(patch: &Value<'a>) -> Result<Vec<Operation<'a>>, PatchError> {
+    patch
+        .as_array()
+        .ok_or(PatchError::NotAnArray)?
+        .iter()
+        .enumerate()
+        .map(|(index, operation)| read_operation(index, operation))
+        .collect()
+}
+
+fn read_operation<'a>(index: usize, operation: &Value<'a>) -> Result<Operation<'a>, PatchError> {
+    let invalid = |reason: String| PatchError::InvalidOperation { index, reason };
+    let members = operation
+        .as_object()
+        .ok_or_else(|| invalid("not an object".to_string()))?;
+    let member = |name: &str| {
+        let mut values = members.iter().filter(|(key, _)| key == name);
+        match (values.next(), values.next()) {
+            (Some(_), Some(_)) => Err(invalid(format!("more than one `{}` member", name))),
+            (Some((_, value)), None) => Ok(value),
+            (None, _) => Err(invalid(format!("missing `{}` member", name))),
+        }
+    };
+    let pointer = |name: &str| match member(name)? {
+        Value::String(pointer) => {
+            Pointer::parse(pointer).map_err(|error| PatchError::Pointer { index, error })
+        }
+        _ => Err(invalid(format!("`{}` is not a string", name))),
+    };
+    let value = || member("value").cloned();
+    let op = match member("op")? {
+        Value::String(op) => op,
+        _ => return Err(invalid("`op` is not a string".to_string())),
+    };
+    let path = pointer("path")?;
+    Ok(match op.as_ref() {
+        "add" => Operation::Add {
+            path,
+            value: value()?,
+        },
+        "remove" => Operation::Remove { path },
+        "replace" => Operation::Replace {
+            path,
+            value: value()?,
+        },
+        "move" => Operation::Move {
+            from: pointer("from")?,
+            path,
+        },
+        "copy" => Operation::Copy {
+            from: pointer("from")?,
+            path,
+        },
+        "test" => Operation::Test {
+            path,
+            value: value()?,
+        },
+        _ => return Err(invalid(format!("unknown op `{}`", op))),
+    })
+}
+
+impl<'a> Operation<'a> {
+    /// The operation as it is written in a JSON patch.
+    pub fn to_value(&self) -> Value<'a> {
+        let (op, path, from, value) = match self {
+            Operation::Add { path, value } => ("add", path, None, Some(value)),
+            Operation::Remove { path } => ("remove", path, None, None),
+            Operation::Replace { path, value } => ("replace", path, None, Some(value)),
+            Operation::Move { from, path } => ("move", path, Some(from), None),
+            Operation::Copy { from, path } => ("copy", path, Some(from), None),
+            Operation::Test { path, value } => ("test", path, None, Some(value)),
+        };
+        let mut members = vec![
+            (Cow::Borrowed("op"), Value::from(op)),
+            (Cow::Borrowed("path"), Value::from(path.to_string())),
+        ];
+        if let Some(from) = from {
+            members.push((Cow::Borrowed("from"), Value::from(from.to_string())));
+        }
+        if let Some(value) = value {
+            members.push((Cow::Borrowed("value"), value.clone()));
+        }
+        Value::Object(members)
+    }
+}
+
+/// Whether two values are equal as JSON patch's `test` compares them.
+///
+/// Numbers are compared by their value rather than their text,
+/// and objects regardless of the order of their members.
+/// Where an object has more than one member with a key, the last is compared.
+pub(crate) fn json_eq(a: &Value<'_>, b: &Value<'_>) -> bool {
+    let mut pairs = vec![(a, b)];
+    while let Some((a, b)) = pairs.pop() {
+        match (a, b) {
+            (Value::Number(x), Value::Number(y)) => {
+                let equal = match (x.as_i128(), y.as_i128()) {
+                    (Some(x), Some(y)) => x == y,
+                    _ => match (x.as_f64(), y.as_f64()) {
+                        (Some(x), Some(y)) => x == y,
+                        _ => x.as_str() == y.as_str(),
+                    },
+                };
+                if !equal {
+                    return false;
+                }
+            }
+            (Value::Array(x), Value::Array(y)) => {
+                if x.len() != y.len() {
+                    return false;
+                }
+                pairs.extend(x.iter().zip(y));
+            }
+            (Value::Object(_), Value::Object(_)) => {
+                let (x, y) = (members_by_key(a), members_by_key(b));
+                if x.len() != y.len() {
+                    return false;
+                }
+                for (key, x) in x {
+                    match y.get(key) {
+                        Some(y) => pairs.push((x, y)),
+                        None => return false,
+                    }
+                }
+            }
+            _ if a != b => return false,
+            _ => (),
+        }
+    }
+    true
+}
+
+/// The value of the last member with each key of an object.
+pub(crate) fn members_by_key<'v, 'a>(value: &'v Value<'a>) -> HashMap<&'v str, &'v Value<'a>> {
+    value.members().collect()
+}
+
+/// The most entries in the table `diff` uses to match up the elements
+/// of two arrays, a million taking 8MB.
+const MAX_TABLE_SIZE: usize = 1 << 20;
+
+/// A JSON patch which turns `old` into `new`.
+///
+/// Members of objects are removed, added or changed where they differ.
+/// Elements of arrays are matched up by their longest common subsequence,
+/// so only the fewest elements are removed or added, and elements
+/// removed and added at the same place are changed in place instead.
+/// This takes time and memory proportional to the product of the
+/// lengths of the arrays, less any elements they start and end with in common,
+/// so past about a million, elements are changed in place by their position,
+/// and the rest removed or added at the end.
+pub fn diff<'a>(old: &Value<'_>, new: &Value<'a>) -> Value<'a> {
+    let mut operations = Vec::new();
+    let mut steps = vec![Step::Diff(Pointer::root(), old, new)];
+    while let Some(step) = steps.pop() {
+        match step {
+            Step::Diff(path, old, new) => {
+                let mut children = Vec::new();
+                diff_step(&mut children, &path, old, new);
+                steps.extend(children.into_iter().rev());
+            }
+            Step::Push(operation) => operations.push(operation),
+        }
+    }
+    operations.iter().map(Operation::to_value).collect()
+}
+
+/// What remains to be done to find a patch, in the order of its operations.
+enum Step<'v, 'a> {
+    /// Find the operations which turn the first value into the second.
+    Diff(Pointer, &'v Value<'v>, &'v Value<'a>),
+    Push(Operation<'a>),
+}
+
+/// The steps which turn `old`, at `path`, into `new`.
+fn diff_step<'v, 'a>(
+    steps: &mut Vec<Step<'v, 'a>>,
+    path: &Pointer,
+    old: &'v Value<'v>,
+    new: &'v Value<'a>,
+) {
+    let child = |token: &str| {
+        let mut child = path.clone();
+        child.push(token);
+        child
+    };
+    match (old, new) {
+        (Value::Object(_), Value::Object(_)) => {
+            let (old_members, new_members) = (members_by_key(old), members_by_key(new));
+            let mut removed = HashSet::new();
+            for (key, _) in old.members() {
+                if !new_members.contains_key(key) && removed.insert(key) {
+                    steps.push(Step::Push(Operation::Remove { path: child(key) }));
+                }
+            }
+            let mut seen = HashSet::new();
+            for (key, _) in new.members() {
+                if !seen.insert(key) {
+                    continue;
+                }
+                let value = new_members[key];
+                steps.push(match old_members.get(key) {
+                    Some(old_value) => Step::Diff(child(key), old_value, value),
+                    None => Step::Push(Operation::Add {
+                        path: child(key),
+                        value: value.clone(),
+                    }),
+                });
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            let prefix = a.iter().zip(b).take_while(|(x, y)| json_eq(x, y)).count();
+            let suffix = a[prefix..]
+                .iter()
+                .rev()
+                .zip(b[prefix..].iter().rev())
+                .take_while(|(x, y)| json_eq(x, y))
+                .count();
+            let a = &a[prefix..a.len() - suffix];
+            let b = &b[prefix..b.len() - suffix];
+            let width = b.len() + 1;
+            if (a.len() + 1).saturating_mul(width) > MAX_TABLE_SIZE {
+                diff_elements(steps, path, prefix, a, b);
+                return;
+            }
+            // `lengths[i * width + j]` is the length of the longest
+            // common subsequence of `a[i..]` and `b[j..]`.
+            let mut lengths = vec![0; (a.len() + 1) * width];
+            for i in (0..a.len()).rev() {
+                for j in (0..b.len()).rev() {
+                    lengths[i * width + j] = if json_eq(&a[i], &b[j]) {
+                        lengths[(i + 1) * width + j + 1] + 1
+                    } else {
+                        lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                    };
+                }
+            }
+            // The elements between those in common, `a[from.0..i]` and
+            // `b[from.1..j]`, are at `from.1` once those before are patched.
+            let (mut i, mut j) = (0, 0);
+            let mut from = (0, 0);
+            loop {
+                let common = i < a.len() && j < b.len() && json_eq(&a[i], &b[j]);
+                if common || (i == a.len() && j == b.len()) {
+                    diff_elements(steps, path, prefix + from.1, &a[from.0..i], &b[from.1..j]);
+                    if !common {
+                        break;
+                    }
+                    i += 1;
+                    j += 1;
+                    from = (i, j);
+                } else if j == b.len()
+                    || (i < a.len() && lengths[(i + 1) * width + j] >= lengths[i * width + j + 1])
+                {
+                    i += 1;
+                } else {
+                    j += 1;
+                }
+            }
+        }
+        _ if json_eq(old, new) => (),
+        _ => steps.push(Step::Push(Operation::Replace {
+            path: path.clone(),
+            value: new.clone(),
+        })),
+    }
+}
+
+/// Turns the elements `old`, starting at `index` of the array at `path`,
+/// into `new`, changing as many as there are in both in place.
+fn diff_elements<'v, 'a>(
+    steps: &mut Vec<Step<'v, 'a>>,
+    path: &Pointer,
+    index: usize,
+    old: &'v [Value<'v>],
+    new: &'v [Value<'a>],
+) {
+    let child = |index: usize| {
+        let mut child = path.clone();
+        child.push(index.to_string());
+        child
+    };
+    let common = old.len().min(new.len());
+    for (i, (x, y)) in old.iter().zip(new).enumerate() {
+        steps.push(Step::Diff(child(index + i), x, y));
+    }
+    // Removing at the same index removes each following element in turn.
+    for _ in common..old.len() {
+        steps.push(Step::Push(Operation::Remove {
+            path: child(index + common),
+        }));
+    }
+    for (i, value) in new.iter().enumerate().skip(common) {
+        steps.push(Step::Push(Operation::Add {
+            path: child(index + i),
+            value: value.clone(),
+        }));
+    }
+}
+
+/// Applies the JSON merge patch `patch` to `target`.
+///
+/// Each member of an object patch sets the member with its key,
+/// merging objects, or removes it where its value is null.
+/// Any other patch replaces `target`.
+pub fn merge_patch<'a>(target: &mut Value<'a>, patch: &Value<'a>) {
+    let patch = match patch {
+        Value::Object(members) => members,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    // The objects being patched, taken out of their parents, each with the
+    // members of its patch left to apply and its index in its parent.
+    let mut stack = vec![(take_members(target), patch.iter(), 0)];
+    while let Some((members, patch, _)) = stack.last_mut() {
+        let (key, value) = match patch.next() {
+            Some(member) => member,
+            None => {
+                let (members, _, index) = stack.pop().unwrap();
+                match stack.last_mut() {
+                    Some((parent, _, _)) => parent[index].1 = Value::Object(members),
+                    None => *target = Value::Object(members),
+                }
+                continue;
+            }
+        };
+        let index = members.iter().rposition(|(old_key, _)| old_key == key);
+        match (index, value) {
+            (_, Value::Null) => members.retain(|(old_key, _)| old_key != key),
+            (Some(index), Value::Object(patch)) => {
+                let child = take_members(&mut members[index].1);
+                stack.push((child, patch.iter(), index));
+            }
+            (None, Value::Object(patch)) => {
+                members.push((key.clone(), Value::Null));
+                let index = members.len() - 1;
+                stack.push((Vec::new(), patch.iter(), index));
+            }
+            (Some(index), _) => members[index].1 = value.clone(),
+            (None, _) => members.push((key.clone(), value.clone())),
+        }
+    }
+}
+
+/// The members of `value` if it is an object, leaving it empty.
+fn take_members<'a>(value: &mut Value<'a>) -> Vec<(Cow<'a, str>, Value<'a>)> {
+    match value {
+        Value::Object(members) => std::mem::take(members),
+        _ => Vec::new(),
+    }
+}
+
+/// A JSON merge patch which turns `old` into `new`.
+///
+/// Merge patches can't set a member to null, since that removes it,
+/// so members of `new` which are null are missing from the patched value.
+pub fn merge_diff<'a>(old: &Value<'_>, new: &Value<'a>) -> Value<'a> {
+    if !matches!((old, new), (Value::Object(_), Value::Object(_))) {
+        return new.clone();
+    }
+    let mut stack = vec![MergeDiff::new(None, old, new)];
+    while let Some(top) = stack.last_mut() {
+        let key = match top.keys.next() {
+            Some(key) => key,
+            None => {
+                let done = stack.pop().unwrap();
+                match (stack.last_mut(), done.key) {
+                    // Objects which are equal need no patch.
+                    (Some(_), _) if done.members.is_empty() => (),
+                    (Some(parent), Some(key)) => {
+                        parent.members.push((key, Value::Object(done.members)))
+                    }
+                    _ => return Value::Object(done.members),
+                }
+                continue;
+            }
+        };
+        let value = top.new[key.as_ref()];
+        match top.old.get(key.as_ref()).copied() {
+            Some(old) if matches!((old, value), (Value::Object(_), Value::Object(_))) => {
+                stack.push(MergeDiff::new(Some(key.clone()), old, value))
+            }
+            Some(old) if json_eq(old, value) => (),
+            _ => top.members.push((key.clone(), value.clone())),
+        }
+    }
+    unreachable!()
+}
+
+/// An object of the patch `merge_diff` is finding, between two objects.
+struct MergeDiff<'v, 'a> {
+    /// The key of the object in its parent.
+    key: Option<Cow<'a, str>>,
+    members: Vec<(Cow<'a, str>, Value<'a>)>,
+    old: HashMap<&'v str, &'v Value<'v>>,
+    new: HashMap<&'v str, &'v Value<'a>>,
+    /// The keys of `new` left to compare, each once.
+    keys: std::vec::IntoIter<&'v Cow<'a, str>>,
+}
+
+impl<'v, 'a> MergeDiff<'v, 'a> {
+    fn new(key: Option<Cow<'a, str>>, old: &'v Value<'v>, new: &'v Value<'a>) -> Self {
+        let (old_members, new_members) = (members_by_key(old), members_by_key(new));
+        let mut members = Vec::new();
+        let mut removed = HashSet::new();
+        for (key, _) in old.members() {
+            if !new_members.contains_key(key) && removed.insert(key) {
+                members.push((Cow::Owned(key.to_string()), Value::Null));
+            }
+        }
+        let mut seen = HashSet::new();
+        let keys: Vec<_> = new
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(key, _)| key)
+            .filter(|&key| seen.insert(key.as_ref()))
+            .collect();
+        MergeDiff {
+            key,
+            members,
+            old: old_members,
+            new: new_members,
+            keys: keys.into_iter(),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use crate::actions;
+use crate::lex::Token;
+use logos::Logos as _;
+use std::fmt;
+use std::ops::Range;
+
+/// One step along a `Path`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// Into the member of an object with the given key.
+    Key(String),
+    /// Into the element of an array at the given index.
+    Index(usize),
+}
+
+/// The location of a value within a document,
+/// as the steps taken from the top level value to reach it.
+///
+/// Displayed as a JSON Pointer (RFC 6901), e.g. `/users/0/name`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Path {
+    pub segments: Vec<PathSegment>,
+}
+
+impl Path {
+    /// The path of the top level value.
+    pub fn root() -> Path {
+        Path::default()
+    }
+
+    pub fn push(&mut self, segment: PathSegment) {
+        self.segments.push(segment)
+    }
+
+    /// The range of the value at this path within `source`,
+    /// found by walking the tokens of `source` without building any values.
+    ///
+    /// Where an object has more than one member with a key,
+    /// the last is found, as `Value::get` does.  Returns `None` if there is
+    /// no value at the path, or the source is invalid before reaching it.
+    pub fn locate(&self, source: &str) -> Option<Range<usize>> {
+        // The start of the value at the path so far.
+        let mut start = 0;
+        for segment in &self.segments {
+            let mut tokens = tokens_from(source, start);
+            start = match (tokens.next()?.0, segment) {
+                (Token::LBrace, PathSegment::Key(key)) => {
+                    let mut found = None;
+                    loop {
+                        let name = match tokens.next()? {
+                            (Token::String(s), range) => actions::string(s, range.start).ok()?,
+                            _ => return None,
+                        };
+                        if tokens.next()?.0 != Token::Colon {
+                            return None;
+                        }
+                        let value = skip_value(&mut tokens)?;
+                        if name == key.as_str() {
+                            found = Some(value.start);
+                        }
+                        match tokens.next()?.0 {
+                            Token::Comma => (),
+                            Token::RBrace => break found?,
+                            _ => return None,
+                        }
+                    }
+                }
+                (Token::LBrack, PathSegment::Index(index)) => {
+                    for _ in 0..*index {
+                        skip_value(&mut tokens)?;
+                        if tokens.next()?.0 != Token::Comma {
+                            return None;
+                        }
+                    }
+                    skip_value(&mut tokens)?.start
+                }
+                _ => return None,
+            };
+        }
+        skip_value(&mut tokens_from(source, start))
+    }
+}
+
+/// The tokens of `source` from `start` on, with their ranges within `source`.
+fn tokens_from(source: &str, start: usize) -> impl Iterator<Item = (Token<'_>, Range<usize>)> {
+    Token::lexer(&source[start..])
+        .spanned()
+        .map(move |(token, range)| (token, start + range.start..start + range.end))
+}
+
+/// Consumes the tokens of the next value, returning its range.
+fn skip_value<'a, I>(tokens: &mut I) -> Option<Range<usize>>
+where
+    I: Iterator<Item = (Token<'a>, Range<usize>)>,
+{
+    let (first, Range { start, mut end }) = tokens.next()?;
+    let mut depth = 0usize;
+    let mut token = first;
+    loop {
+        match token {
+            Token::LBrace | Token::LBrack => depth += 1,
+            Token::RBrace | Token::RBrack => depth = depth.checked_sub(1)?,
+            Token::Error | Token::MissingEndQuote(_) => return None,
+            Token::Colon | Token::Comma if depth == 0 => return None,
+            _ => (),
+        }
+        if depth == 0 {
+            return Some(start..end);
+        }
+        let (next, range) = tokens.next()?;
+        token = next;
+        end = range.end;
+    }
+}
+
+impl fmt::Display for PathSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PathSegment::Key(key) => write!(f, "{}", key.replace('~', "~0").replace('/', "~1")),
+            PathSegment::Index(index) => write!(f, "{}", index),
+        }
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for segment in &self.segments {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! JSON Pointers (RFC 6901), such as `/spec/containers/0/image`,
+//! for finding and changing values nested within a `Value`.
+use crate::path::{Path, PathSegment};
+use crate::value::Value;
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// A parsed JSON Pointer, the unescaped reference tokens between each `/`.
+///
+/// The empty pointer refers to the whole document.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Pointer {
+    tokens: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerError {
+    /// A pointer other than the empty pointer must start with `/`.
+    MissingSlash,
+    /// A `~` in the token wasn't followed by `0` or `1`.
+    InvalidEscape { segment: usize, token: String },
+    /// There is no member with the token as its key,
+    /// or no element with the token as its index.
+    NotFound { segment: usize, token: String },
+    /// The value before the token is neither an array nor an object.
+    NotAContainer { segment: usize, token: String },
+    /// The token is not an array index, which are written in decimal
+    /// without leading zeros, or `-` for the end of the array when inserting.
+    InvalidIndex { segment: usize, token: String },
+    /// The whole document can't be removed.
+    RemoveRoot,
+}
+
+impl fmt::Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Segments are displayed counting from 1.
+        match self {
+            PointerError::MissingSlash => write!(f, "a JSON pointer must start with `/`"),
+            PointerError::InvalidEscape { segment, token } => write!(
+                f,
+                "segment {} `{}`: `~` must be followed by `0` or `1`",
+                segment + 1,
+                token
+            ),
+            PointerError::NotFound { segment, token } => {
+                write!(
+                    f,
+                    "segment {} `{}`: no such member or element",
+                    segment + 1,
+                    token
+                )
+            }
+            PointerError::NotAContainer { segment, token } => write!(
+                f,
+                "segment {} `{}`: not within an array or object",
+                segment + 1,
+                token
+            ),
+            PointerError::InvalidIndex { segment, token } => {
+                write!(f, "segment {} `{}`: not an array index", segment + 1, token)
+            }
+            PointerError::RemoveRoot => write!(f, "the whole document can't be removed"),
+        }
+    }
+}
+
+impl std::error::Error for PointerError {}
+
+/// The index of an existing element, or of the end of the array for `-`.
+fn parse_index(token: &str, len: usize, segment: usize) -> Result<usize, PointerError> {
+    let valid = match token.as_bytes() {
+        b"-" => return Ok(len),
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    };
+    match token.parse() {
+        Ok(index) if valid => Ok(index),
+        _ => Err(PointerError::InvalidIndex {
+            segment,
+            token: token.to_string(),
+        }),
+    }
+}
+
+impl Pointer {
+    /// The empty pointer, referring to the whole document.
+    pub fn root() -> Pointer {
+        Pointer::default()
+    }
+
+    pub fn parse(pointer: &str) -> Result<Pointer, PointerError> {
+        if pointer.is_empty() {
+            return Ok(Pointer::root());
+        }
+        if !pointer.starts_with('/') {
+            return Err(PointerError::MissingSlash);
+        }
+        let tokens = pointer[1..]
+            .split('/')
+            .enumerate()
+            .map(|(segment, escaped)| {
+                let invalid = escaped.match_indices('~').any(|(i, _)| {
+                    !matches!(escaped.as_bytes().get(i + 1), Some(b'0') | Some(b'1'))
+                });
+                if invalid {
+                    Err(PointerError::InvalidEscape {
+                        segment,
+                        token: escaped.to_string(),
+                    })
+                } else {
+                    Ok(escaped.replace("~1", "/").replace("~0", "~"))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Pointer { tokens })
+    }
+
+    /// The unescaped reference tokens.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    pub fn push<S: Into<String>>(&mut self, token: S) {
+        self.tokens.push(token.into())
+    }
+
+    /// The pointer to the value containing this one, and this one's token,
+    /// or `None` for the root.
+    pub fn split_last(&self) -> Option<(Pointer, &str)> {
+        let (last, parent) = self.tokens.split_last()?;
+        Some((
+            Pointer {
+                tokens: parent.to_vec(),
+            },
+            last,
+        ))
+    }
+
+    /// The value this pointer refers to.
+    pub fn get<'v, 'a>(&self, value: &'v Value<'a>) -> Result<&'v Value<'a>, PointerError> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .try_fold(value, |value, (segment, token)| {
+                let not_found = || PointerError::NotFound {
+                    segment,
+                    token: token.clone(),
+                };
+                match value {
+                    Value::Object(_) => value.get(token.as_str()).ok_or_else(not_found),
+                    Value::Array(array) => array
+                        .get(parse_index(token, array.len(), segment)?)
+                        .ok_or_else(not_found),
+                    _ => Err(PointerError::NotAContainer {
+                        segment,
+                        token: token.clone(),
+                    }),
+                }
+            })
+    }
+
+    pub fn get_mut<'v, 'a>(
+        &self,
+        value: &'v mut Value<'a>,
+    ) -> Result<&'v mut Value<'a>, PointerError> {
+        self.tokens
+            .iter()
+            .enumerate()
+            .try_fold(value, |value, (segment, token)| {
+                let not_found = || PointerError::NotFound {
+                    segment,
+                    token: token.clone(),
+                };
+                match value {
+                    Value::Object(_) => value.get_mut(token.as_str()).ok_or_else(not_found),
+                    Value::Array(array) => {
+                        let index = parse_index(token, array.len(), segment)?;
+                        array.get_mut(index).ok_or_else(not_found)
+                    }
+                    _ => Err(PointerError::NotAContainer {
+                        segment,
+                        token: token.clone(),
+                    }),
+                }
+            })
+    }
+
+    /// Adds `new` at this pointer, as the `add` operation of JSON Patch does.
+    ///
+    /// Into an object this sets the member with the key, replacing the last
+    /// member with that key if any.  Into an array this inserts at the index,
+    /// where `-` or the length of the array appends.
+    /// At the root this replaces the whole document.
+    pub fn insert<'a>(&self, value: &mut Value<'a>, new: Value<'a>) -> Result<(), PointerError> {
+        let (parent, token) = match self.split_last() {
+            Some(split) => split,
+            None => {
+                *value = new;
+                return Ok(());
+            }
+        };
+        let segment = parent.tokens.len();
+        match parent.get_mut(value)? {
+            Value::Object(obj) => match obj.iter_mut().rev().find(|(key, _)| key == token) {
+                Some((_, old)) => *old = new,
+                None => obj.push((Cow::Owned(token.to_string()), new)),
+            },
+            Value::Array(array) => {
+                let index = parse_index(token, array.len(), segment)?;
+                if index > array.len() {
+                    return Err(PointerError::NotFound {
+                        segment,
+                        token: token.to_string(),
+                    });
+                }
+                array.insert(index, new);
+            }
+            _ => {
+                return Err(PointerError::NotAContainer {
+                    segment,
+                    token: token.to_string(),
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the value at this pointer, returning it.
+    ///
+    /// Every member of an object with the key is removed,
+    /// and the last of them is returned.
+    pub fn remove<'a>(&self, value: &mut Value<'a>) -> Result<Value<'a>, PointerError> {
+        let (parent, token) = self.split_last().ok_or(PointerError::RemoveRoot)?;
+        let segment = parent.tokens.len();
+        let not_found = || PointerError::NotFound {
+            segment,
+            token: token.to_string(),
+        };
+        match parent.get_mut(value)? {
+            Value::Object(obj) => {
+                let mut removed = None;
+                let mut i = 0;
+                while i < obj.len() {
+                    if obj[i].0 == token {
+                        removed = Some(obj.remove(i).1);
+                    } else {
+                        i += 1;
+                    }
+                }
+                removed.ok_or_else(not_found)
+            }
+            Value::Array(array) => {
+                let index = parse_index(token, array.len(), segment)?;
+                if index < array.len() {
+                    Ok(array.remove(index))
+                } else {
+                    Err(not_found())
+                }
+            }
+            _ => Err(PointerError::NotAContainer {
+                segment,
+                token: token.to_string(),
+            }),
+        }
+    }
+
+    /// Replaces the existing value at this pointer with `new`,
+    /// returning the old value.
+    pub fn replace<'a>(
+        &self,
+        value: &mut Value<'a>,
+        new: Value<'a>,
+    ) -> Result<Value<'a>, PointerError> {
+        let old = self.get_mut(value)?;
+        Ok(std::mem::replace(old, new))
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = PointerError;
+
+    fn from_str(pointer: &str) -> Result<Pointer, PointerError> {
+        Pointer::parse(pointer)
+    }
+}
+
+impl fmt::Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.tokens {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&Path> for Pointer {
+    fn from(path: &Path) -> Pointer {
+        Pointer {
+            tokens: path
+                .segments
+                .iter()
+                .map(|segment| match segment {
+                    PathSegment::Key(key) => key.clone(),
+                    PathSegment::Index(index) => index.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<'a> Value<'a> {
+    /// The value at a JSON Pointer such as `/spec/containers/0/image`,
+    /// see `Pointer::get`.
+    pub fn pointer(&self, pointer: &str) -> Result<&Value<'a>, PointerError> {
+        Pointer::parse(pointer)?.get(self)
+    }
+
+    pub fn pointer_mut(&mut self, pointer: &str) -> Result<&mut Value<'a>, PointerError> {
+        Pointer::parse(pointer)?.get_mut(self)
+    }
+
+    /// See `Pointer::insert`.
+    pub fn pointer_insert(&mut self, pointer: &str, new: Value<'a>) -> Result<(), PointerError> {
+        Pointer::parse(pointer)?.insert(self, new)
+    }
+
+    /// See `Pointer::remove`.
+    pub fn pointer_remove(&mut self, pointer: &str) -> Result<Value<'a>, PointerError> {
+        Pointer::parse(pointer)?.remove(self)
+    }
+
+    /// See `Pointer::replace`.
+    pub fn pointer_replace(
+        &mut self,
+        pointer: &str,
+        new: Value<'a>,
+    ) -> Result<Value<'a>, PointerError> {
+        Pointer::parse(pointer)?.replace(self, new)
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+/// Maps byte offsets within a source to lines and columns.
+#[derive(Debug, Clone)]
+pub struct LineIndex<'a> {
+    source: &'a str,
+    /// The byte offset at which each line starts.
+    line_starts: Vec<usize>,
+}
+
+/// A zero based line and column.
+///
+/// Displayed one based, as "line 3, column 14".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> LineIndex<'a> {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        LineIndex {
+            source,
+            line_starts,
+        }
+    }
+
+    /// The position of `offset`, with the column counted in characters.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let (line, before) = self.line_prefix(offset);
+        LineCol {
+            line,
+            column: before.chars().count(),
+        }
+    }
+
+    /// The position of `offset`, with the column counted in bytes.
+    pub fn line_col_utf8(&self, offset: usize) -> LineCol {
+        let (line, before) = self.line_prefix(offset);
+        LineCol {
+            line,
+            column: before.len(),
+        }
+    }
+
+    /// The position of `offset`, with the column counted in UTF-16 code units,
+    /// as used by many editors.
+    pub fn line_col_utf16(&self, offset: usize) -> LineCol {
+        let (line, before) = self.line_prefix(offset);
+        LineCol {
+            line,
+            column: before.encode_utf16().count(),
+        }
+    }
+
+    /// The line containing `offset`, and the text of that line preceding it.
+    ///
+    /// Offsets past the end of the source are clamped to the end,
+    /// and offsets within a character are moved back to its start.
+    fn line_prefix(&self, offset: usize) -> (usize, &'a str) {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        (line, &self.source[self.line_starts[line]..offset])
+    }
+}
+
+impl fmt::Display for LineCol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line + 1, self.column + 1)
+    }
+}
@@ -0,0 +1,174 @@
+//! Parsing past errors, so that every error in the source can be reported
+//! at once, see `parse_str_recovering`.
+//!
+//! `recovering.lalrpop` is the grammar of `json.lalrpop` with LALRPOP's `!`
+//! symbol in place of a value or an object member, its actions record
+//! the errors recovered from rather than failing.  `parse_str` keeps to
+//! the grammar without recovery, which stops at the first error.
+use crate::brackets::{Bracketed, Seen};
+use crate::error::CompilationError;
+use crate::lex::Token;
+use crate::options::{self, DuplicateKeys, Member, Triple};
+use crate::parser::{ErrorRecovery, ParseError};
+use crate::value::Value;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// The result of `parse_str_recovering`.
+#[derive(Debug)]
+pub struct Recovered<'a> {
+    /// The value parsed, with `Null` in place of each value which couldn't be
+    /// and without object members which couldn't be.  Strings containing
+    /// invalid escapes are kept as they were written.
+    ///
+    /// `None` if the parser couldn't recover, as when a limit
+    /// of `ParseOptions` is exceeded.
+    pub value: Option<Value<'a>>,
+    /// Every error found, in the order of their position in the source.
+    pub errors: Vec<Bracketed<'a>>,
+    /// The range in the source of each `Null` put in place of a value,
+    /// in order, telling them apart from those written as `null`.
+    pub placeholders: Vec<Range<usize>>,
+}
+
+/// What the recovering parser has found besides the value,
+/// as recorded by the actions of `recovering.lalrpop`.
+#[derive(Debug, Default)]
+pub struct Recovery<'a> {
+    errors: Vec<ErrorRecovery<'a>>,
+    placeholders: Vec<Range<usize>>,
+}
+
+impl<'a> Recovered<'a> {
+    /// Gathers the errors of a parse over `tokens`, which were cut off
+    /// at `limit` if one was exceeded, with the `brackets` given to the parser.
+    pub(crate) fn new(
+        result: Result<Value<'a>, ParseError<'a>>,
+        recovery: Recovery<'a>,
+        limit: Option<CompilationError>,
+        brackets: &[Seen],
+    ) -> Recovered<'a> {
+        let Recovery {
+            errors: recovered,
+            mut placeholders,
+        } = recovery;
+        // The tokens skipped while recovering opened and closed nothing.
+        let dropped: HashSet<usize> = recovered
+            .iter()
+            .flat_map(|recovery| recovery.dropped_tokens.iter().map(|(start, _, _)| *start))
+            .collect();
+        let mut errors: Vec<_> = recovered.into_iter().map(|r| r.error).collect();
+        let mut value = match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                errors.push(lexical_error(error));
+                None
+            }
+        };
+        if let Some(limit) = limit {
+            // The input didn't end where the tokens were cut off.
+            errors
+                .retain(|error| !matches!(error, lalrpop_util::ParseError::UnrecognizedEOF { .. }));
+            errors.push(lalrpop_util::ParseError::User { error: limit });
+            value = None;
+        }
+        errors.sort_by_key(|error| crate::error::parse_error_range(error).start);
+        placeholders.sort_by_key(|range| range.start);
+        Recovered {
+            value,
+            errors: Bracketed::attach(brackets, &dropped, errors),
+            placeholders,
+        }
+    }
+
+    /// The value, if it was parsed without any errors.
+    pub fn into_result(mut self) -> Result<Value<'a>, Vec<Bracketed<'a>>> {
+        match self.value.take() {
+            Some(value) if self.errors.is_empty() => Ok(value),
+            _ => Err(self.errors),
+        }
+    }
+}
+
+/// Tokens as given by `options::tokens`, for the recovering parser.
+///
+/// Invalid tokens are given to the parser as `Token::Error`, rather than
+/// failing the token stream, so that it can recover after them.
+/// The tokens end at the first limit exceeded, which is kept in `limit`:
+/// failing the stream would lose any error the parser was recovering from.
+pub(crate) fn tokens<'a, 'o>(
+    tokens: impl Iterator<Item = Triple<'a>> + 'o,
+    limit: &'o Cell<Option<CompilationError>>,
+) -> impl Iterator<Item = Triple<'a>> + 'o {
+    tokens.scan((), move |(), triple| match triple {
+        Ok(triple) => Some(Ok(triple)),
+        Err(CompilationError::LexicalError { range }) => {
+            Some(Ok((range.start, Token::Error, range.end)))
+        }
+        Err(error) => {
+            limit.set(Some(error));
+            None
+        }
+    })
+}
+
+/// Turns the parser's error at a `Token::Error` back into a `LexicalError`.
+pub fn lexical_error(error: ParseError<'_>) -> ParseError<'_> {
+    match error {
+        lalrpop_util::ParseError::UnrecognizedToken {
+            token: (start, Token::Error, end),
+            ..
+        }
+        | lalrpop_util::ParseError::ExtraToken {
+            token: (start, Token::Error, end),
+        } => lalrpop_util::ParseError::User {
+            error: CompilationError::LexicalError { range: start..end },
+        },
+        error => error,
+    }
+}
+
+impl<'a> Recovery<'a> {
+    /// Records an error the parser recovered from.
+    pub fn recovered(&mut self, mut recovery: ErrorRecovery<'a>) {
+        recovery.error = lexical_error(recovery.error);
+        self.errors.push(recovery);
+    }
+
+    /// Records an error found by an action of the grammar.
+    pub fn record(&mut self, error: CompilationError) {
+        self.recovered(ErrorRecovery {
+            error: lalrpop_util::ParseError::User { error },
+            dropped_tokens: Vec::new(),
+        })
+    }
+
+    /// A placeholder for the value at `range`, which couldn't be parsed.
+    pub fn placeholder(&mut self, range: Range<usize>) -> Value<'a> {
+        self.placeholders.push(range);
+        Value::Null
+    }
+
+    /// The members of an object as given by `ParseOptions::duplicate_keys`,
+    /// without those which couldn't be parsed.
+    ///
+    /// Rejected duplicate keys are recorded, and kept.
+    pub fn object(
+        &mut self,
+        policy: DuplicateKeys,
+        members: Vec<Option<Member<'a>>>,
+    ) -> Vec<(Cow<'a, str>, Value<'a>)> {
+        let members: Vec<_> = members.into_iter().flatten().collect();
+        let policy = match options::duplicate_key(policy, &members) {
+            Some(error) => {
+                self.record(error);
+                DuplicateKeys::KeepAll
+            }
+            None => policy,
+        };
+        options::resolve_duplicate_keys(policy, members)
+            .expect("only rejecting duplicate keys fails")
+    }
+}
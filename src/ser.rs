@@ -0,0 +1,218 @@
+use crate::escape;
+use crate::value::Value;
+use std::borrow::Cow;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+mod serializer;
+#[cfg(feature = "serde")]
+pub use serializer::{
+    to_string, to_string_pretty, to_string_with, to_writer, to_writer_pretty, to_writer_with,
+    Compound, Error, Serializer,
+};
+
+/// Options controlling how a `Value` is written out.
+///
+/// The default writes a value on a single line, as `Value`'s `Display` does.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Escape every non-ASCII character as `\uXXXX`,
+    /// for transports which only carry ASCII.
+    pub ascii_only: bool,
+    /// Put each element and member on its own line, indented by nesting depth,
+    /// or write the value on a single line when `None`.
+    pub indent: Option<Indent>,
+    /// Write a space after the `:` following each key.
+    pub space_after_colon: bool,
+    /// On a single line, write a space after each `,`.
+    pub space_after_comma: bool,
+    /// The line break written between lines.
+    pub newline: Newline,
+    /// End the output with a line break.
+    pub trailing_newline: bool,
+}
+
+/// The indentation of each level of nesting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    Tab,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Newline {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl Newline {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            ascii_only: false,
+            indent: None,
+            space_after_colon: true,
+            space_after_comma: true,
+            newline: Newline::Lf,
+            trailing_newline: false,
+        }
+    }
+}
+
+impl Config {
+    /// Indented by two spaces with a trailing newline, much like `jq .`.
+    pub fn pretty() -> Config {
+        Config {
+            indent: Some(Indent::Spaces(2)),
+            trailing_newline: true,
+            ..Config::default()
+        }
+    }
+
+    fn write_line_break<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        indent: Indent,
+        depth: usize,
+    ) -> fmt::Result {
+        w.write_str(self.newline.as_str())?;
+        for _ in 0..depth {
+            match indent {
+                Indent::Spaces(n) => write!(w, "{:1$}", "", n)?,
+                Indent::Tab => w.write_char('\t')?,
+            }
+        }
+        Ok(())
+    }
+
+    /// What comes between a key and its value.
+    pub(crate) fn colon(&self) -> &'static str {
+        if self.space_after_colon {
+            ": "
+        } else {
+            ":"
+        }
+    }
+
+    /// Writes what comes before an element or member at `depth`.
+    pub(crate) fn write_separator<W: fmt::Write>(
+        &self,
+        w: &mut W,
+        first: bool,
+        depth: usize,
+    ) -> fmt::Result {
+        if !first {
+            w.write_char(',')?;
+        }
+        match self.indent {
+            Some(indent) => self.write_line_break(w, indent, depth),
+            None if !first && self.space_after_comma => w.write_char(' '),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes what comes before the closing bracket of a non-empty array or object.
+    pub(crate) fn write_closing<W: fmt::Write>(&self, w: &mut W, depth: usize) -> fmt::Result {
+        match self.indent {
+            Some(indent) => self.write_line_break(w, indent, depth),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The arrays and objects being written, with the elements which remain.
+enum Frame<'v, 'a> {
+    Array(std::slice::Iter<'v, Value<'a>>),
+    Object(std::slice::Iter<'v, (Cow<'a, str>, Value<'a>)>),
+}
+
+/// Writes `value` as JSON text according to `config`.
+///
+/// Nested values are kept on a heap allocated stack rather than recursing,
+/// so deeply nested values cannot overflow the call stack.
+pub fn write_value<W: fmt::Write>(w: &mut W, value: &Value<'_>, config: &Config) -> fmt::Result {
+    let mut stack = Vec::new();
+    let mut next = Some(value);
+    loop {
+        if let Some(value) = next.take() {
+            match value {
+                Value::Number(number) => w.write_str(number.as_str())?,
+                Value::String(string) => escape::write_escaped(w, string, config.ascii_only)?,
+                Value::Bool(flag) => write!(w, "{}", flag)?,
+                Value::Null => w.write_str("null")?,
+                Value::Array(array) if array.is_empty() => w.write_str("[]")?,
+                Value::Object(obj) if obj.is_empty() => w.write_str("{}")?,
+                Value::Array(array) => {
+                    w.write_char('[')?;
+                    stack.push((Frame::Array(array.iter()), true));
+                }
+                Value::Object(obj) => {
+                    w.write_char('{')?;
+                    stack.push((Frame::Object(obj.iter()), true));
+                }
+            }
+        }
+
+        let depth = stack.len();
+        let (frame, first) = match stack.last_mut() {
+            Some(top) => top,
+            None => break,
+        };
+        match frame {
+            Frame::Array(elements) => match elements.next() {
+                Some(value) => {
+                    config.write_separator(w, *first, depth)?;
+                    next = Some(value);
+                }
+                None => {
+                    config.write_closing(w, depth - 1)?;
+                    w.write_char(']')?;
+                    stack.pop();
+                    continue;
+                }
+            },
+            Frame::Object(members) => match members.next() {
+                Some((key, value)) => {
+                    config.write_separator(w, *first, depth)?;
+                    escape::write_escaped(w, key, config.ascii_only)?;
+                    w.write_str(config.colon())?;
+                    next = Some(value);
+                }
+                None => {
+                    config.write_closing(w, depth - 1)?;
+                    w.write_char('}')?;
+                    stack.pop();
+                    continue;
+                }
+            },
+        }
+        *first = false;
+    }
+    if config.trailing_newline {
+        w.write_str(config.newline.as_str())?;
+    }
+    Ok(())
+}
+
+/// A `Value` paired with a `Config`, which writes the value
+/// according to the config when displayed.
+pub struct Formatted<'v, 'a> {
+    pub value: &'v Value<'a>,
+    pub config: &'v Config,
+}
+
+impl<'v, 'a> fmt::Display for Formatted<'v, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self.value, self.config)
+    }
+}
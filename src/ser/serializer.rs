@@ -0,0 +1,671 @@
+//! A `serde::Serializer` writing JSON text as `write_value` does,
+//! with the same escaping, number formatting and `Config`.
+use super::Config;
+use crate::escape;
+use serde::ser::{self, Serialize};
+use std::fmt;
+use std::io;
+
+/// Serializes `value` on a single line, as `Value`'s `Display` does.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    to_string_with(value, &Config::default())
+}
+
+/// Serializes `value` indented as by `Config::pretty`.
+pub fn to_string_pretty<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    to_string_with(value, &Config::pretty())
+}
+
+pub fn to_string_with<T: Serialize + ?Sized>(value: &T, config: &Config) -> Result<String, Error> {
+    let mut ser = Serializer::new(String::new(), config.clone());
+    value.serialize(&mut ser)?;
+    ser.finish()?;
+    Ok(ser.into_inner())
+}
+
+/// Like `to_string`, but writes to `writer`.
+pub fn to_writer<W: io::Write, T: Serialize + ?Sized>(writer: W, value: &T) -> Result<(), Error> {
+    to_writer_with(writer, value, &Config::default())
+}
+
+/// Like `to_string_pretty`, but writes to `writer`.
+pub fn to_writer_pretty<W: io::Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+) -> Result<(), Error> {
+    to_writer_with(writer, value, &Config::pretty())
+}
+
+pub fn to_writer_with<W: io::Write, T: Serialize + ?Sized>(
+    writer: W,
+    value: &T,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut ser = Serializer::new(
+        IoWriter {
+            inner: writer,
+            error: None,
+        },
+        config.clone(),
+    );
+    // The writer keeps the io error behind the `fmt::Error` it returned.
+    let result = value.serialize(&mut ser).and_then(|()| ser.finish());
+    match (result, ser.writer.error) {
+        (Err(Error::Fmt), Some(err)) => Err(Error::Io(err)),
+        (result, _) => result,
+    }
+}
+
+/// Adapts an `io::Write` to the `fmt::Write` which the escaping is written to.
+struct IoWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// The underlying `fmt::Write` failed.
+    Fmt,
+    /// Infinities and NaN can't be written as JSON.
+    NonFiniteFloat,
+    /// A map key which can't be written as a string.
+    KeyMustBeAString,
+    /// An error from a `Serialize` implementation.
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Fmt => write!(f, "error writing output"),
+            Error::NonFiniteFloat => write!(f, "infinite or NaN floats can't be written as JSON"),
+            Error::KeyMustBeAString => write!(f, "object keys must be strings"),
+            Error::Custom(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Error {
+        Error::Fmt
+    }
+}
+
+/// Writes serde data model values as JSON text according to a `Config`.
+pub struct Serializer<W> {
+    writer: W,
+    config: Config,
+    /// The number of arrays and objects currently open.
+    depth: usize,
+}
+
+impl<W: fmt::Write> Serializer<W> {
+    pub fn new(writer: W, config: Config) -> Serializer<W> {
+        Serializer {
+            writer,
+            config,
+            depth: 0,
+        }
+    }
+
+    /// Ends the output as the config asks, after a value has been serialized.
+    pub fn finish(&mut self) -> Result<(), Error> {
+        if self.config.trailing_newline {
+            self.writer.write_str(self.config.newline.as_str())?;
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn write_float(&mut self, float: f64, text: String) -> Result<(), Error> {
+        if float.is_finite() {
+            Ok(self.writer.write_str(&text)?)
+        } else {
+            Err(Error::NonFiniteFloat)
+        }
+    }
+
+    fn write_str(&mut self, s: &str) -> Result<(), Error> {
+        Ok(escape::write_escaped(
+            &mut self.writer,
+            s,
+            self.config.ascii_only,
+        )?)
+    }
+
+    fn open(&mut self, bracket: char) -> Result<(), Error> {
+        self.writer.write_char(bracket)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn separator(&mut self, first: bool) -> Result<(), Error> {
+        Ok(self
+            .config
+            .write_separator(&mut self.writer, first, self.depth)?)
+    }
+
+    fn close(&mut self, bracket: char, empty: bool) -> Result<(), Error> {
+        self.depth -= 1;
+        if !empty {
+            self.config.write_closing(&mut self.writer, self.depth)?;
+        }
+        Ok(self.writer.write_char(bracket)?)
+    }
+
+    fn colon(&mut self) -> Result<(), Error> {
+        Ok(self.writer.write_str(self.config.colon())?)
+    }
+
+    /// Opens the object around a variant, as `{"variant": `.
+    fn open_variant(&mut self, variant: &str) -> Result<(), Error> {
+        self.open('{')?;
+        self.separator(true)?;
+        self.write_str(variant)?;
+        self.colon()
+    }
+}
+
+impl<'s, W: fmt::Write> ser::Serializer for &'s mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'s, W>;
+    type SerializeTuple = Compound<'s, W>;
+    type SerializeTupleStruct = Compound<'s, W>;
+    type SerializeTupleVariant = Compound<'s, W>;
+    type SerializeMap = Compound<'s, W>;
+    type SerializeStruct = Compound<'s, W>;
+    type SerializeStructVariant = Compound<'s, W>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", v)?)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", v)?)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", v)?)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.serialize_u64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", v)?)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), Error> {
+        Ok(write!(self.writer, "{}", v)?)
+    }
+
+    /// As the shortest text which reads back as the same `f32`.
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.write_float(v.into(), lexical::to_string(v))
+    }
+
+    /// As `value::Number::from_f64` writes it.
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.write_float(v, lexical::to_string(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.write_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.write_str(v)
+    }
+
+    /// As an array of numbers.
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        use serde::ser::SerializeSeq as _;
+        let mut seq = self.serialize_seq(Some(v.len()))?;
+        for byte in v {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(self.writer.write_str("null")?)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.write_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.open_variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.close('}', false)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, W>, Error> {
+        self.open('[')?;
+        Ok(Compound::new(self, ']', false))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'s, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'s, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>, Error> {
+        self.open_variant(variant)?;
+        self.open('[')?;
+        Ok(Compound::new(self, ']', true))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, W>, Error> {
+        self.open('{')?;
+        Ok(Compound::new(self, '}', false))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'s, W>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, W>, Error> {
+        self.open_variant(variant)?;
+        self.open('{')?;
+        Ok(Compound::new(self, '}', true))
+    }
+}
+
+/// An array or object being serialized.
+pub struct Compound<'s, W> {
+    ser: &'s mut Serializer<W>,
+    /// Whether no elements or members have been written yet.
+    first: bool,
+    close: char,
+    /// Whether this is the value of a variant, in an object of its own.
+    in_variant: bool,
+}
+
+impl<'s, W: fmt::Write> Compound<'s, W> {
+    fn new(ser: &'s mut Serializer<W>, close: char, in_variant: bool) -> Compound<'s, W> {
+        Compound {
+            ser,
+            first: true,
+            close,
+            in_variant,
+        }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.ser.separator(self.first)?;
+        self.first = false;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn field<T: Serialize + ?Sized>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.ser.separator(self.first)?;
+        self.first = false;
+        self.ser.write_str(key)?;
+        self.ser.colon()?;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.ser.close(self.close, self.first)?;
+        if self.in_variant {
+            self.ser.close('}', false)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeSeq for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeTuple for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeTupleStruct for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeTupleVariant for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeMap for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.ser.separator(self.first)?;
+        self.first = false;
+        key.serialize(MapKeySerializer {
+            ser: &mut *self.ser,
+        })?;
+        self.ser.colon()
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeStruct for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, W: fmt::Write> ser::SerializeStructVariant for Compound<'s, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Compound::end(self)
+    }
+}
+
+/// Writes map keys, which must be strings, or integers and chars
+/// which are written as strings.
+struct MapKeySerializer<'s, W> {
+    ser: &'s mut Serializer<W>,
+}
+
+macro_rules! key_as_string {
+    ($($method:ident($ty:ty))*) => {
+        $(
+            fn $method(self, v: $ty) -> Result<(), Error> {
+                self.ser.write_str(&v.to_string())
+            }
+        )*
+    };
+}
+
+impl<'s, W: fmt::Write> ser::Serializer for MapKeySerializer<'s, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = ser::Impossible<(), Error>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    key_as_string! {
+        serialize_i8(i8) serialize_i16(i16) serialize_i32(i32) serialize_i64(i64)
+        serialize_i128(i128) serialize_u8(u8) serialize_u16(u16) serialize_u32(u32)
+        serialize_u64(u64) serialize_u128(u128) serialize_char(char)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.ser.write_str(v)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.ser.write_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, _value: &T) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::KeyMustBeAString)
+    }
+}
@@ -0,0 +1,785 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::BuildHasher;
+use std::mem;
+use std::ops;
+
+/// `Clone`, `PartialEq`, `into_owned` and `as_borrowed` don't recurse
+/// into nested values, so they take any depth of nesting.  `Debug` and
+/// `serde::Serialize` do, see `ParseOptions::max_depth`.
+#[derive(Debug)]
+pub enum Value<'a> {
+    Number(Number<'a>),
+    /// The decoded string, borrowed from the source when it contains no escapes.
+    String(Cow<'a, str>),
+    Object(Vec<(Cow<'a, str>, Value<'a>)>),
+    Bool(bool),
+    Null,
+    Array(Vec<Value<'a>>),
+}
+
+/// A `Value` which owns all of its strings,
+/// so it can outlive the source it was parsed from.
+pub type OwnedValue = Value<'static>;
+
+impl<'a> Value<'a> {
+    /// Copies any strings borrowed from the source,
+    /// giving a value which can be kept after the source is dropped.
+    pub fn into_owned(self) -> OwnedValue {
+        rebuild(
+            self,
+            // Values can't be moved out of, since `Value` implements `Drop`.
+            |mut value| match &mut value {
+                Value::Number(number) => {
+                    Node::Leaf(Value::Number(number.as_borrowed().into_owned()))
+                }
+                Value::String(string) => {
+                    Node::Leaf(Value::String(Cow::Owned(mem::take(string).into_owned())))
+                }
+                Value::Object(obj) => Node::Object(mem::take(obj).into_iter()),
+                Value::Bool(flag) => Node::Leaf(Value::Bool(*flag)),
+                Value::Null => Node::Leaf(Value::Null),
+                Value::Array(array) => Node::Array(mem::take(array).into_iter()),
+            },
+            |(key, value): (Cow<'a, str>, Value<'a>)| (Cow::Owned(key.into_owned()), value),
+        )
+    }
+
+    /// A copy of the value whose strings borrow from this one,
+    /// such as a `Value` borrowing from an `OwnedValue`.
+    pub fn as_borrowed(&self) -> Value<'_> {
+        rebuild(
+            self,
+            |value| match value {
+                Value::Number(number) => Node::Leaf(Value::Number(number.as_borrowed())),
+                Value::String(string) => Node::Leaf(Value::String(Cow::Borrowed(string))),
+                Value::Object(obj) => Node::Object(obj.iter()),
+                Value::Bool(flag) => Node::Leaf(Value::Bool(*flag)),
+                Value::Null => Node::Leaf(Value::Null),
+                Value::Array(array) => Node::Array(array.iter()),
+            },
+            |(key, value): &(Cow<'a, str>, Value<'a>)| (Cow::Borrowed(key.as_ref()), value),
+        )
+    }
+}
+
+impl<'a> Clone for Value<'a> {
+    fn clone(&self) -> Value<'a> {
+        rebuild(
+            self,
+            |value| match value {
+                Value::Number(number) => Node::Leaf(Value::Number(number.clone())),
+                Value::String(string) => Node::Leaf(Value::String(string.clone())),
+                Value::Object(obj) => Node::Object(obj.iter()),
+                Value::Bool(flag) => Node::Leaf(Value::Bool(*flag)),
+                Value::Null => Node::Leaf(Value::Null),
+                Value::Array(array) => Node::Array(array.iter()),
+            },
+            |(key, value): &(Cow<'a, str>, Value<'a>)| (key.clone(), value),
+        )
+    }
+}
+
+/// Compares arrays element by element and objects member by member,
+/// in order, as a derived `PartialEq` would.
+impl<'a> PartialEq for Value<'a> {
+    fn eq(&self, other: &Value<'a>) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (Value::Array(x), Value::Array(y)) if x.len() == y.len() => {
+                    stack.extend(x.iter().zip(y))
+                }
+                (Value::Object(x), Value::Object(y)) if x.len() == y.len() => {
+                    for ((x_key, x), (y_key, y)) in x.iter().zip(y) {
+                        if x_key != y_key {
+                            return false;
+                        }
+                        stack.push((x, y));
+                    }
+                }
+                (Value::Number(x), Value::Number(y)) if x == y => (),
+                (Value::String(x), Value::String(y)) if x == y => (),
+                (Value::Bool(x), Value::Bool(y)) if x == y => (),
+                (Value::Null, Value::Null) => (),
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+/// A value taken apart by `rebuild`.
+enum Node<'b, E, M> {
+    Leaf(Value<'b>),
+    Array(E),
+    Object(M),
+}
+
+/// A partly built array or object, and what is left to build of it.
+enum Building<'b, E, M> {
+    Array(Vec<Value<'b>>, E),
+    Object(Vec<(Cow<'b, str>, Value<'b>)>, M, Option<Cow<'b, str>>),
+}
+
+/// Builds a copy of `root`, taking each value apart with `split` and each
+/// member with `member`.  The arrays and objects being built are kept on a
+/// heap allocated stack rather than recursing, as for `Drop`.
+fn rebuild<'b, T, E, M>(
+    root: T,
+    mut split: impl FnMut(T) -> Node<'b, E, M>,
+    mut member: impl FnMut(M::Item) -> (Cow<'b, str>, T),
+) -> Value<'b>
+where
+    E: ExactSizeIterator<Item = T>,
+    M: ExactSizeIterator,
+{
+    let mut stack = Vec::new();
+    let mut next = root;
+    loop {
+        let mut built = match split(next) {
+            Node::Leaf(value) => Some(value),
+            Node::Array(elements) => {
+                stack.push(Building::Array(
+                    Vec::with_capacity(elements.len()),
+                    elements,
+                ));
+                None
+            }
+            Node::Object(members) => {
+                stack.push(Building::Object(
+                    Vec::with_capacity(members.len()),
+                    members,
+                    None,
+                ));
+                None
+            }
+        };
+        // Adds each value built to its array or object, until one has
+        // another value to build.
+        next = loop {
+            let child = match stack.last_mut() {
+                None => return built.expect("the root is built last"),
+                Some(Building::Array(array, elements)) => {
+                    array.extend(built.take());
+                    elements.next()
+                }
+                Some(Building::Object(obj, members, key)) => {
+                    if let Some(value) = built.take() {
+                        obj.push((key.take().expect("a value follows its key"), value));
+                    }
+                    members.next().map(|next| {
+                        let (next_key, value) = member(next);
+                        *key = Some(next_key);
+                        value
+                    })
+                }
+            };
+            match child {
+                Some(child) => break child,
+                None => {
+                    built = Some(match stack.pop() {
+                        Some(Building::Array(array, _)) => Value::Array(array),
+                        Some(Building::Object(obj, _, _)) => Value::Object(obj),
+                        None => unreachable!(),
+                    })
+                }
+            }
+        };
+    }
+}
+
+/// Returned by indexing with a key or index which isn't present.
+static NULL: Value<'static> = Value::Null;
+
+impl<'a> Value<'a> {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(flag) => Some(*flag),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<&Number<'a>> {
+        match self {
+            Value::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// See `Number::as_f64`.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().and_then(Number::as_f64)
+    }
+
+    /// See `Number::as_i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    /// See `Number::as_u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Value<'a>>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value<'a>>> {
+        match self {
+            Value::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Vec<(Cow<'a, str>, Value<'a>)>> {
+        match self {
+            Value::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    pub fn as_object_mut(&mut self) -> Option<&mut Vec<(Cow<'a, str>, Value<'a>)>> {
+        match self {
+            Value::Object(obj) => Some(obj),
+            _ => None,
+        }
+    }
+
+    /// The member of an object with a `&str` key, or the element of an array
+    /// at a `usize` index.
+    ///
+    /// Where an object has more than one member with the key,
+    /// the last is returned, as most JSON implementations keep the last.
+    pub fn get<I: ValueIndex>(&self, index: I) -> Option<&Value<'a>> {
+        index.index_into(self)
+    }
+
+    /// Like `get`, but mutable.
+    pub fn get_mut<I: ValueIndex>(&mut self, index: I) -> Option<&mut Value<'a>> {
+        index.index_into_mut(self)
+    }
+
+    /// The keys and values of an object, or nothing for other values.
+    pub fn members(&self) -> impl Iterator<Item = (&str, &Value<'a>)> {
+        self.as_object()
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| (key.as_ref(), value))
+    }
+
+    /// The elements of an array, or nothing for other values.
+    pub fn elements(&self) -> std::slice::Iter<'_, Value<'a>> {
+        match self {
+            Value::Array(array) => array.iter(),
+            _ => [].iter(),
+        }
+    }
+
+    /// Replaces the value with `Null`, returning it.
+    ///
+    /// Since `Value` implements `Drop`, its contents can't be moved out by
+    /// a pattern, this moves them out of a `&mut Value` instead.
+    pub fn take(&mut self) -> Value<'a> {
+        mem::replace(self, Value::Null)
+    }
+}
+
+/// Types which can index into a `Value`, see `Value::get`.
+pub trait ValueIndex {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>>;
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>>;
+}
+
+impl ValueIndex for usize {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        match value {
+            Value::Array(array) => array.get(*self),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        match value {
+            Value::Array(array) => array.get_mut(*self),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for str {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        match value {
+            Value::Object(obj) => obj
+                .iter()
+                .rev()
+                .find(|(key, _)| key == self)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        match value {
+            Value::Object(obj) => obj
+                .iter_mut()
+                .rev()
+                .find(|(key, _)| key == self)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+}
+
+impl ValueIndex for String {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        self.as_str().index_into(value)
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        self.as_str().index_into_mut(value)
+    }
+}
+
+impl<I: ValueIndex + ?Sized> ValueIndex for &I {
+    fn index_into<'v, 'a>(&self, value: &'v Value<'a>) -> Option<&'v Value<'a>> {
+        (**self).index_into(value)
+    }
+
+    fn index_into_mut<'v, 'a>(&self, value: &'v mut Value<'a>) -> Option<&'v mut Value<'a>> {
+        (**self).index_into_mut(value)
+    }
+}
+
+/// Like `Value::get`, but returns `Null` for a missing key or index,
+/// so lookups can be chained as in `value["users"][0]["name"]`.
+impl<'a, I: ValueIndex> ops::Index<I> for Value<'a> {
+    type Output = Value<'a>;
+
+    fn index(&self, index: I) -> &Value<'a> {
+        index.index_into(self).unwrap_or(&NULL)
+    }
+}
+
+impl<'a> From<bool> for Value<'a> {
+    fn from(flag: bool) -> Value<'a> {
+        Value::Bool(flag)
+    }
+}
+
+impl<'a> From<Number<'a>> for Value<'a> {
+    fn from(number: Number<'a>) -> Value<'a> {
+        Value::Number(number)
+    }
+}
+
+macro_rules! value_from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl<'a> From<$ty> for Value<'a> {
+                fn from(int: $ty) -> Self {
+                    Value::Number(int.into())
+                }
+            }
+        )*
+    };
+}
+
+value_from_integer!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Infinities and NaN, which JSON can't represent, become `Null`.
+impl<'a> From<f64> for Value<'a> {
+    fn from(float: f64) -> Value<'a> {
+        Number::from_f64(float).map_or(Value::Null, Value::Number)
+    }
+}
+
+/// Infinities and NaN, which JSON can't represent, become `Null`.
+impl<'a> From<f32> for Value<'a> {
+    fn from(float: f32) -> Value<'a> {
+        f64::from(float).into()
+    }
+}
+
+impl<'a> From<&'a str> for Value<'a> {
+    fn from(string: &'a str) -> Value<'a> {
+        Value::String(Cow::Borrowed(string))
+    }
+}
+
+impl<'a> From<String> for Value<'a> {
+    fn from(string: String) -> Value<'a> {
+        Value::String(Cow::Owned(string))
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Value<'a> {
+    fn from(string: Cow<'a, str>) -> Value<'a> {
+        Value::String(string)
+    }
+}
+
+impl<'a> From<()> for Value<'a> {
+    fn from((): ()) -> Value<'a> {
+        Value::Null
+    }
+}
+
+impl<'a, T: Into<Value<'a>>> From<Option<T>> for Value<'a> {
+    fn from(option: Option<T>) -> Value<'a> {
+        option.map_or(Value::Null, Into::into)
+    }
+}
+
+impl<'a, T: Into<Value<'a>>> From<Vec<T>> for Value<'a> {
+    fn from(vec: Vec<T>) -> Value<'a> {
+        vec.into_iter().collect()
+    }
+}
+
+impl<'a, K, V, S> From<HashMap<K, V, S>> for Value<'a>
+where
+    K: Into<Cow<'a, str>>,
+    V: Into<Value<'a>>,
+    S: BuildHasher,
+{
+    fn from(map: HashMap<K, V, S>) -> Value<'a> {
+        map.into_iter().collect()
+    }
+}
+
+impl<'a, K, V> From<BTreeMap<K, V>> for Value<'a>
+where
+    K: Into<Cow<'a, str>>,
+    V: Into<Value<'a>>,
+{
+    fn from(map: BTreeMap<K, V>) -> Value<'a> {
+        map.into_iter().collect()
+    }
+}
+
+/// Collects into an array.
+impl<'a, T: Into<Value<'a>>> std::iter::FromIterator<T> for Value<'a> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Value<'a> {
+        Value::Array(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Collects keys and values into an object.
+impl<'a, K: Into<Cow<'a, str>>, V: Into<Value<'a>>> std::iter::FromIterator<(K, V)> for Value<'a> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Value<'a> {
+        Value::Object(
+            iter.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<'a> Drop for Value<'a> {
+    fn drop(&mut self) {
+        // Dropping nested values recursively could overflow the call stack,
+        // so their elements are moved onto a heap allocated stack and dropped
+        // once they have no elements of their own.
+        let mut stack = match self {
+            Value::Array(array) => mem::take(array),
+            Value::Object(obj) => obj.drain(..).map(|(_, value)| value).collect(),
+            _ => return,
+        };
+        while let Some(mut value) = stack.pop() {
+            match &mut value {
+                Value::Array(array) => stack.append(array),
+                Value::Object(obj) => stack.extend(obj.drain(..).map(|(_, value)| value)),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        crate::ser::write_value(f, self, &crate::ser::Config::default())
+    }
+}
+
+/// A number, kept as the text it was written as so that no precision is lost.
+///
+/// Numbers are compared by their text, so `1.0` and `1` are not equal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Number<'a>(Cow<'a, str>);
+
+impl<'a> Number<'a> {
+    /// Returns `None` if `lexeme` isn't a number as specified by JSON.
+    pub fn new<S: Into<Cow<'a, str>>>(lexeme: S) -> Option<Number<'a>> {
+        let lexeme = lexeme.into();
+        if is_number(lexeme.as_bytes()) {
+            Some(Number(lexeme))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `None` for infinities and NaN which JSON cannot represent.
+    pub fn from_f64(float: f64) -> Option<Number<'static>> {
+        if float.is_finite() {
+            Some(Number(Cow::Owned(lexical::to_string(float))))
+        } else {
+            None
+        }
+    }
+
+    pub fn into_owned(self) -> Number<'static> {
+        Number(Cow::Owned(self.0.into_owned()))
+    }
+
+    pub fn as_borrowed(&self) -> Number<'_> {
+        Number(Cow::Borrowed(&self.0))
+    }
+
+    /// The number as it was written.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The nearest `f64`, which is infinite if the number is out of range.
+    pub fn as_f64(&self) -> Option<f64> {
+        lexical::parse(self.0.as_bytes()).ok()
+    }
+
+    /// Returns `None` if the number isn't an integer literal in range of an `i64`.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.0.parse().ok()
+    }
+
+    /// Returns `None` if the number isn't an integer literal in range of a `u64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.0.parse().ok()
+    }
+
+    /// Returns `None` if the number isn't an integer literal in range of an `i128`.
+    pub fn as_i128(&self) -> Option<i128> {
+        self.0.parse().ok()
+    }
+
+    /// Returns `None` if the number isn't an integer literal in range of a `u128`.
+    pub fn as_u128(&self) -> Option<u128> {
+        self.0.parse().ok()
+    }
+
+    /// Whether the number is written without a fraction or exponent.
+    pub fn is_integer(&self) -> bool {
+        !self.0.contains(&['.', 'e', 'E'][..])
+    }
+}
+
+impl<'a> fmt::Display for Number<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+macro_rules! number_from_integer {
+    ($($ty:ty)*) => {
+        $(
+            impl From<$ty> for Number<'static> {
+                fn from(int: $ty) -> Self {
+                    Number(Cow::Owned(int.to_string()))
+                }
+            }
+        )*
+    };
+}
+
+number_from_integer!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+// -?(0|[1-9][0-9]*)(\.[0-9]+)?([eE][+-]?[0-9]+)?
+fn is_number(bytes: &[u8]) -> bool {
+    let digits = |i: usize| {
+        bytes[i..]
+            .iter()
+            .position(|b| !b.is_ascii_digit())
+            .map_or(bytes.len(), |len| i + len)
+    };
+
+    let mut i = if bytes.first() == Some(&b'-') { 1 } else { 0 };
+    i = match bytes.get(i) {
+        Some(b'0') => i + 1,
+        Some(b'1'..=b'9') => digits(i),
+        _ => return false,
+    };
+    if bytes.get(i) == Some(&b'.') {
+        let end = digits(i + 1);
+        if end == i + 1 {
+            return false;
+        }
+        i = end;
+    }
+    if let Some(b'e') | Some(b'E') = bytes.get(i) {
+        i += 1;
+        if let Some(b'+') | Some(b'-') = bytes.get(i) {
+            i += 1;
+        }
+        let end = digits(i);
+        if end == i {
+            return false;
+        }
+        i = end;
+    }
+    i == bytes.len()
+}
+
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use super::{Number, Value};
+    use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+    use serde::ser::{Serialize, SerializeMap as _, SerializeSeq as _, Serializer};
+    use std::borrow::Cow;
+    use std::fmt;
+    use std::marker::PhantomData;
+
+    impl<'a> Serialize for Number<'a> {
+        /// As the narrowest of `u64`, `i64`, `i128` or `f64` which holds the number.
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            if let Some(n) = self.as_u64() {
+                serializer.serialize_u64(n)
+            } else if let Some(n) = self.as_i64() {
+                serializer.serialize_i64(n)
+            } else if let Some(n) = self.as_i128() {
+                serializer.serialize_i128(n)
+            } else {
+                match self.as_f64() {
+                    Some(x) if x.is_finite() => serializer.serialize_f64(x),
+                    _ => Err(serde::ser::Error::custom(format_args!(
+                        "number {} is out of range",
+                        self
+                    ))),
+                }
+            }
+        }
+    }
+
+    impl<'a> Serialize for Value<'a> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            match self {
+                Value::Number(number) => number.serialize(serializer),
+                Value::String(string) => serializer.serialize_str(string),
+                Value::Bool(flag) => serializer.serialize_bool(*flag),
+                Value::Null => serializer.serialize_unit(),
+                Value::Array(array) => {
+                    let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                    for element in array {
+                        seq.serialize_element(element)?;
+                    }
+                    seq.end()
+                }
+                Value::Object(obj) => {
+                    let mut map = serializer.serialize_map(Some(obj.len()))?;
+                    for (key, value) in obj {
+                        map.serialize_entry(key.as_ref(), value)?;
+                    }
+                    map.end()
+                }
+            }
+        }
+    }
+
+    struct ValueVisitor<'a>(PhantomData<Value<'a>>);
+
+    impl<'de, 'a> Visitor<'de> for ValueVisitor<'a> {
+        type Value = Value<'a>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("any JSON value")
+        }
+
+        fn visit_unit<E: de::Error>(self) -> Result<Value<'a>, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_none<E: de::Error>(self) -> Result<Value<'a>, E> {
+            Ok(Value::Null)
+        }
+
+        fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value<'a>, D::Error> {
+            Deserialize::deserialize(deserializer)
+        }
+
+        fn visit_bool<E: de::Error>(self, flag: bool) -> Result<Value<'a>, E> {
+            Ok(Value::Bool(flag))
+        }
+
+        fn visit_i64<E: de::Error>(self, n: i64) -> Result<Value<'a>, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_u64<E: de::Error>(self, n: u64) -> Result<Value<'a>, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_i128<E: de::Error>(self, n: i128) -> Result<Value<'a>, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_u128<E: de::Error>(self, n: u128) -> Result<Value<'a>, E> {
+            Ok(Value::Number(n.into()))
+        }
+
+        fn visit_f64<E: de::Error>(self, x: f64) -> Result<Value<'a>, E> {
+            match Number::from_f64(x) {
+                Some(number) => Ok(Value::Number(number)),
+                None => Err(E::invalid_value(
+                    de::Unexpected::Float(x),
+                    &"a finite number",
+                )),
+            }
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<Value<'a>, E> {
+            Ok(Value::String(Cow::Owned(s.to_string())))
+        }
+
+        fn visit_string<E: de::Error>(self, s: String) -> Result<Value<'a>, E> {
+            Ok(Value::String(Cow::Owned(s)))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value<'a>, A::Error> {
+            let mut array = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+            while let Some(element) = seq.next_element()? {
+                array.push(element);
+            }
+            Ok(Value::Array(array))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value<'a>, A::Error> {
+            let mut obj = Vec::with_capacity(map.size_hint().unwrap_or(0).min(4096));
+            while let Some((key, value)) = map.next_entry::<String, _>()? {
+                obj.push((Cow::Owned(key), value));
+            }
+            Ok(Value::Object(obj))
+        }
+    }
+
+    /// Strings are always copied, as by `Cow`'s `Deserialize`,
+    /// so that an `OwnedValue` can be deserialized from any deserializer.
+    impl<'de, 'a> Deserialize<'de> for Value<'a> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value<'a>, D::Error> {
+            deserializer.deserialize_any(ValueVisitor(PhantomData))
+        }
+    }
+}